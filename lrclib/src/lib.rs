@@ -0,0 +1,54 @@
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use async_std::task;
+use reqwest::{Client, StatusCode};
+use serde_derive::Deserialize;
+use url::Url;
+use metadata_fetch::LyricsFetcher;
+
+const USER_AGENT: &str = concat!("harborz/", env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/ravenblackdusk/harborz )");
+
+pub struct Lrclib {
+    base_uri: Url,
+    client: Client,
+}
+
+impl Default for Lrclib {
+    fn default() -> Self {
+        Self {
+            base_uri: Url::parse("https://lrclib.net").unwrap(),
+            client: Client::builder().user_agent(USER_AGENT).build().unwrap(),
+        }
+    }
+}
+
+impl Lrclib {
+    async fn get(&self, artist: &str, album: &str, title: &str, duration: Duration) -> anyhow::Result<Option<String>> {
+        let mut uri = self.base_uri.join("/api/get").unwrap();
+        uri.query_pairs_mut().append_pair("artist_name", artist).append_pair("track_name", title)
+            .append_pair("album_name", album).append_pair("duration", &duration.as_secs().to_string());
+        let response = self.client.get(uri.as_str()).send().await?;
+        if response.status() == StatusCode::NOT_FOUND { return Ok(None); }
+        let LyricsResponse { synced_lyrics, plain_lyrics } = response.error_for_status()?.json().await?;
+        Ok(synced_lyrics.or(plain_lyrics).filter(|it| { !it.trim().is_empty() }))
+    }
+}
+
+impl LyricsFetcher for Lrclib {
+    fn name(&self) -> &'static str {
+        "LRCLIB"
+    }
+    fn download_lyrics(&'static self, artist: &str, album: &str, title: &str, duration: Duration,
+        sender: Sender<anyhow::Result<Option<String>>>) {
+        let (artist, album, title) = (artist.to_owned(), album.to_owned(), title.to_owned());
+        task::spawn(async move { sender.send(self.get(&artist, &album, &title, duration).await).unwrap(); });
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LyricsResponse {
+    synced_lyrics: Option<String>,
+    plain_lyrics: Option<String>,
+}
@@ -1,8 +1,14 @@
 use std::sync::mpsc::Sender;
+use std::time::Duration;
 use std::usize;
 use bytes::Bytes;
 
+mod registry;
+
+pub use registry::Registry;
+
 pub struct ArtistSearch {
+    pub provider: &'static str,
     pub name: String,
     pub genre: String,
     pub location: String,
@@ -15,6 +21,7 @@ pub enum DownloadArtistEvent {
 }
 
 pub struct AlbumSearch {
+    pub provider: &'static str,
     pub artist: String,
     pub album: String,
     pub album_type: String,
@@ -26,6 +33,39 @@ pub enum DownloadAlbumEvent {
 }
 
 pub trait MetadataFetcher {
+    fn name(&self) -> &'static str;
     fn download_artist_logo_and_photo(&'static self, artist: &str, sender: Sender<DownloadArtistEvent>);
     fn download_cover(&'static self, artist: &str, album: &str, sender: Sender<DownloadAlbumEvent>);
 }
+
+pub struct ProposedTrack {
+    pub title: String,
+    pub artist: Option<String>,
+    pub track_number: Option<i32>,
+    pub album_volume: Option<i32>,
+    pub duration: Option<Duration>,
+}
+
+pub struct ProposedRelease {
+    pub provider: &'static str,
+    pub id: String,
+    pub artist: String,
+    pub album: String,
+    pub year: Option<i32>,
+    pub tracks: Vec<ProposedTrack>,
+}
+
+pub trait ReleaseLookup {
+    fn name(&self) -> &'static str;
+    // either artist or album may be empty to look up by the other alone
+    fn lookup_release(&'static self, artist: &str, album: &str,
+        sender: Sender<anyhow::Result<Vec<ProposedRelease>>>);
+    fn lookup_release_by_durations(&'static self, durations: &[Duration],
+        sender: Sender<anyhow::Result<Vec<ProposedRelease>>>);
+}
+
+pub trait LyricsFetcher {
+    fn name(&self) -> &'static str;
+    fn download_lyrics(&'static self, artist: &str, album: &str, title: &str, duration: Duration,
+        sender: Sender<anyhow::Result<Option<String>>>);
+}
@@ -0,0 +1,118 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use crate::{DownloadAlbumEvent, DownloadArtistEvent, MetadataFetcher};
+
+type SearchResult<S> = anyhow::Result<Vec<anyhow::Result<S>>>;
+
+trait DownloadEvent: Sized + Send + 'static {
+    type Search: Send + 'static;
+    fn into_search_result(self) -> Result<SearchResult<Self::Search>, Self>;
+    fn from_search_result(search_result: SearchResult<Self::Search>) -> Self;
+    fn offset(self, offset: usize) -> Self;
+}
+
+impl DownloadEvent for DownloadArtistEvent {
+    type Search = crate::ArtistSearch;
+    fn into_search_result(self) -> Result<SearchResult<Self::Search>, Self> {
+        match self {
+            DownloadArtistEvent::SearchResult(search_result) => { Ok(search_result) }
+            event => { Err(event) }
+        }
+    }
+    fn from_search_result(search_result: SearchResult<Self::Search>) -> Self {
+        DownloadArtistEvent::SearchResult(search_result)
+    }
+    fn offset(self, offset: usize) -> Self {
+        match self {
+            DownloadArtistEvent::Logo(i, logo) => { DownloadArtistEvent::Logo(i + offset, logo) }
+            DownloadArtistEvent::Photo(i, photo) => { DownloadArtistEvent::Photo(i + offset, photo) }
+            event => { event }
+        }
+    }
+}
+
+impl DownloadEvent for DownloadAlbumEvent {
+    type Search = crate::AlbumSearch;
+    fn into_search_result(self) -> Result<SearchResult<Self::Search>, Self> {
+        match self {
+            DownloadAlbumEvent::SearchResult(search_result) => { Ok(search_result) }
+            event => { Err(event) }
+        }
+    }
+    fn from_search_result(search_result: SearchResult<Self::Search>) -> Self {
+        DownloadAlbumEvent::SearchResult(search_result)
+    }
+    fn offset(self, offset: usize) -> Self {
+        match self {
+            DownloadAlbumEvent::Cover(i, cover) => { DownloadAlbumEvent::Cover(i + offset, cover) }
+            event => { event }
+        }
+    }
+}
+
+// image indices are shifted past the results of the providers before
+fn fan_in<E: DownloadEvent>(receivers: Vec<Receiver<E>>, sender: Sender<E>) {
+    thread::spawn(move || {
+        let mut searches = Vec::new();
+        let mut errors = Vec::new();
+        let mut forwards = Vec::new();
+        for receiver in receivers {
+            let mut buffered = Vec::new();
+            let mut search_result = None;
+            while let Ok(event) = receiver.recv() {
+                match event.into_search_result() {
+                    Ok(result) => {
+                        search_result = Some(result);
+                        break;
+                    }
+                    Err(event) => { buffered.push(event); }
+                }
+            }
+            let offset = searches.len();
+            match search_result {
+                Some(Ok(search_vec)) => { searches.extend(search_vec); }
+                Some(Err(error)) => { errors.push(error); }
+                None => {}
+            }
+            forwards.push((offset, buffered, receiver));
+        }
+        let search_result = if searches.is_empty() && !errors.is_empty() {
+            Err(errors.remove(0))
+        } else {
+            Ok(searches)
+        };
+        if sender.send(E::from_search_result(search_result)).is_err() { return; }
+        for (offset, buffered, receiver) in forwards {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                for event in buffered.into_iter().chain(receiver) {
+                    if sender.send(event.offset(offset)).is_err() { break; }
+                }
+            });
+        }
+    });
+}
+
+pub struct Registry {
+    providers: Vec<&'static dyn MetadataFetcher>,
+}
+
+impl Registry {
+    pub fn new(providers: Vec<&'static dyn MetadataFetcher>) -> Self {
+        Self { providers }
+    }
+    pub fn download_artist_logo_and_photo(&self, artist: &str, sender: Sender<DownloadArtistEvent>) {
+        fan_in(self.providers.iter().map(|provider| {
+            let (provider_sender, receiver) = channel();
+            provider.download_artist_logo_and_photo(artist, provider_sender);
+            receiver
+        }).collect(), sender);
+    }
+    pub fn download_cover(&self, artist: &str, album: &str, sender: Sender<DownloadAlbumEvent>) {
+        fan_in(self.providers.iter().map(|provider| {
+            let (provider_sender, receiver) = channel();
+            provider.download_cover(artist, album, provider_sender);
+            receiver
+        }).collect(), sender);
+    }
+}
@@ -65,6 +65,9 @@ struct SearchResponse {
 }
 
 impl MetadataFetcher for MetalArchives {
+    fn name(&self) -> &'static str {
+        "Metal Archives"
+    }
     fn download_artist_logo_and_photo(&'static self, artist: &str, sender: Sender<DownloadArtistEvent>) {
         let mut uri = self.base_uri.join("/search/ajax-band-search").unwrap();
         uri.query_pairs_mut().append_pair("field", "name").append_pair("query", artist);
@@ -94,6 +97,7 @@ impl MetadataFetcher for MetalArchives {
                                 }
                             }));
                             Ok(ArtistSearch {
+                                provider: self.name(),
                                 name,
                                 genre,
                                 location,
@@ -129,6 +133,7 @@ impl MetadataFetcher for MetalArchives {
                                 }
                             }));
                             Ok(AlbumSearch {
+                                provider: self.name(),
                                 artist: Self::text(artist_fragment.select(&Self::a()).next().unwrap()),
                                 album,
                                 album_type,
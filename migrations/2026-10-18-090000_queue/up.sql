@@ -0,0 +1,20 @@
+create table queue
+(
+    id       integer not null
+        constraint queue_pk
+            primary key autoincrement,
+    song_id  integer not null
+        constraint queue_songs_id_fk
+            references songs
+            on update cascade on delete cascade,
+    position integer not null
+);
+
+create index queue_position_index
+    on queue (position);
+
+alter table config
+    add current_queue_id integer
+        constraint config_queue_id_fk
+            references queue
+            on update cascade on delete set null;
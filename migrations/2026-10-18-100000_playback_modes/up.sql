@@ -0,0 +1,8 @@
+alter table config
+    add repeat_mode TEXT default 'off' not null;
+
+alter table config
+    add shuffle_seed sqlite_uint64;
+
+alter table config
+    add shuffle_start integer;
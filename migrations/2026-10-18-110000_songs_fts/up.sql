@@ -0,0 +1,39 @@
+create virtual table songs_fts using fts5
+(
+    title,
+    artist,
+    album,
+    genre,
+    lyrics,
+    content = 'songs',
+    content_rowid = 'id'
+);
+
+insert into songs_fts(songs_fts)
+values ('rebuild');
+
+create trigger songs_fts_insert
+    after insert
+    on songs
+begin
+    insert into songs_fts(rowid, title, artist, album, genre, lyrics)
+    values (new.id, new.title, new.artist, new.album, new.genre, new.lyrics);
+end;
+
+create trigger songs_fts_delete
+    after delete
+    on songs
+begin
+    insert into songs_fts(songs_fts, rowid, title, artist, album, genre, lyrics)
+    values ('delete', old.id, old.title, old.artist, old.album, old.genre, old.lyrics);
+end;
+
+create trigger songs_fts_update
+    after update
+    on songs
+begin
+    insert into songs_fts(songs_fts, rowid, title, artist, album, genre, lyrics)
+    values ('delete', old.id, old.title, old.artist, old.album, old.genre, old.lyrics);
+    insert into songs_fts(rowid, title, artist, album, genre, lyrics)
+    values (new.id, new.title, new.artist, new.album, new.genre, new.lyrics);
+end;
@@ -0,0 +1,16 @@
+create table import_issues
+(
+    id            integer           not null
+        constraint import_issues_pk
+            primary key autoincrement,
+    collection_id integer           not null
+        constraint import_issues_collections_id_fk
+            references collections
+            on update cascade on delete cascade,
+    path          TEXT              not null,
+    reason        TEXT              not null,
+    ignored       integer default 0 not null
+);
+
+create unique index import_issues_collection_id_path_uindex
+    on import_issues (collection_id, path);
@@ -0,0 +1,68 @@
+create table albums
+(
+    id            integer not null
+        constraint albums_pk
+            primary key autoincrement,
+    collection_id integer not null
+        constraint albums_collections_id_fk
+            references collections
+            on update cascade on delete cascade,
+    artist        TEXT,
+    title         TEXT,
+    disambiguator TEXT    not null,
+    year          integer,
+    cover         TEXT    not null,
+    total_tracks  integer
+);
+
+create index albums_collection_id_disambiguator_index
+    on albums (collection_id, disambiguator);
+
+alter table songs
+    add album_id integer
+        constraint songs_albums_id_fk
+            references albums
+            on update cascade on delete set null;
+
+create index songs_album_id_index
+    on songs (album_id);
+
+create temp table song_albums as
+select id,
+       collection_id,
+       coalesce(album_artist, artist)               as artist,
+       album                                        as title,
+       year,
+       rtrim(rtrim(path, replace(path, '/', '')), '/') as directory
+from songs;
+
+-- songs in disc sub directories such as "CD1" or "Disc 2" belong to the album in the parent directory
+update song_albums
+set directory = rtrim(rtrim(directory, replace(directory, '/', '')), '/')
+where lower(substr(directory, length(rtrim(directory, replace(directory, '/', ''))) + 1)) glob 'cd*[0-9]*'
+   or lower(substr(directory, length(rtrim(directory, replace(directory, '/', ''))) + 1)) glob 'dis[ck]*[0-9]*';
+
+insert into albums(collection_id, artist, title, disambiguator, year, cover)
+select collection_id,
+       artist,
+       title,
+       directory,
+       min(year),
+       case when directory = '' then 'cover.jpg' else directory || '/cover.jpg' end
+from song_albums
+group by collection_id, artist, title, directory;
+
+update songs
+set album_id = (select albums.id
+                from song_albums
+                         inner join albums on albums.collection_id = song_albums.collection_id
+                    and albums.artist is song_albums.artist and albums.title is song_albums.title
+                    and albums.disambiguator = song_albums.directory
+                where song_albums.id = songs.id);
+
+drop table song_albums;
+
+-- songs bodies are restored from their album id from now on
+delete
+from bodies
+where body_type = 'songs';
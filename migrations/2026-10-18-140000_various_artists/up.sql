@@ -0,0 +1,62 @@
+alter table config
+    add various_artists TEXT default 'grouped' not null;
+
+-- songs without an album artist join the first album with their title in their directory, as on import
+update songs
+set album_id = (select min(other.id)
+                from albums
+                         inner join albums other on other.collection_id = albums.collection_id
+                    and other.title is albums.title and other.disambiguator = albums.disambiguator
+                where albums.id = songs.album_id)
+where album_artist is null
+  and album_id is not null;
+
+delete
+from albums
+where not exists (select * from songs where songs.album_id = albums.id);
+
+create temp table primary_artists as
+select album_id, artist as primary_artist
+from songs
+where album_id is not null;
+
+-- featured artists do not make an album a compilation
+update primary_artists
+set primary_artist = rtrim(substr(primary_artist, 1, instr(lower(primary_artist), ' feat. ') - 1))
+where instr(lower(primary_artist), ' feat. ') > 1;
+update primary_artists
+set primary_artist = rtrim(substr(primary_artist, 1, instr(lower(primary_artist), ' feat ') - 1))
+where instr(lower(primary_artist), ' feat ') > 1;
+update primary_artists
+set primary_artist = rtrim(substr(primary_artist, 1, instr(lower(primary_artist), ' ft. ') - 1))
+where instr(lower(primary_artist), ' ft. ') > 1;
+update primary_artists
+set primary_artist = rtrim(substr(primary_artist, 1, instr(lower(primary_artist), ' featuring ') - 1))
+where instr(lower(primary_artist), ' featuring ') > 1;
+update primary_artists
+set primary_artist = rtrim(substr(primary_artist, 1, instr(lower(primary_artist), ' (feat. ') - 1))
+where instr(lower(primary_artist), ' (feat. ') > 1;
+update primary_artists
+set primary_artist = rtrim(substr(primary_artist, 1, instr(lower(primary_artist), ' (ft. ') - 1))
+where instr(lower(primary_artist), ' (ft. ') > 1;
+update primary_artists
+set primary_artist = rtrim(substr(primary_artist, 1, instr(lower(primary_artist), ' [feat. ') - 1))
+where instr(lower(primary_artist), ' [feat. ') > 1;
+
+update albums
+set artist = coalesce((select case
+                                  when lower(album_artist) in ('various artists', 'various', 'va')
+                                      then 'Various Artists'
+                                  else album_artist end
+                       from songs
+                       where songs.album_id = albums.id
+                         and album_artist is not null
+                       order by songs.id
+                       limit 1),
+                      (select case
+                                  when count(distinct ifnull(primary_artist, '')) > 1 then 'Various Artists'
+                                  else max(primary_artist) end
+                       from primary_artists
+                       where primary_artists.album_id = albums.id));
+
+drop table primary_artists;
@@ -0,0 +1,27 @@
+create table merges
+(
+    id    integer not null
+        constraint merges_pk
+            primary key autoincrement,
+    field TEXT    not null,
+    value TEXT    not null
+);
+
+create table merge_songs
+(
+    id       integer not null
+        constraint merge_songs_pk
+            primary key autoincrement,
+    merge_id integer not null
+        constraint merge_songs_merges_id_fk
+            references merges
+            on update cascade on delete cascade,
+    song_id  integer not null
+        constraint merge_songs_songs_id_fk
+            references songs
+            on update cascade on delete cascade,
+    original TEXT
+);
+
+create index merge_songs_merge_id_index
+    on merge_songs (merge_id);
@@ -0,0 +1,12 @@
+create table providers
+(
+    name     TEXT    not null
+        constraint providers_pk
+            primary key,
+    position integer not null,
+    enabled  integer default 1 not null
+);
+
+insert into providers(name, position)
+values ('Metal Archives', 0),
+       ('Local folder', 1);
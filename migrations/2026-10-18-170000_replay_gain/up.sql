@@ -0,0 +1,8 @@
+alter table songs
+    add track_gain REAL;
+alter table songs
+    add track_peak REAL;
+alter table songs
+    add album_gain REAL;
+alter table songs
+    add album_peak REAL;
@@ -0,0 +1,7 @@
+alter table songs
+    add analysis_failed integer default 0 not null;
+
+alter table config
+    add loudness_analysis integer default 0 not null;
+alter table config
+    add write_replay_gain integer default 0 not null;
@@ -0,0 +1,2 @@
+alter table config
+    add crossfade integer default 0 not null;
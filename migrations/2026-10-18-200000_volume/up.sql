@@ -0,0 +1,4 @@
+alter table config
+    add volume REAL default 1 not null;
+alter table config
+    add mute integer default 0 not null;
@@ -0,0 +1,16 @@
+create table loops
+(
+    id             integer not null
+        constraint loops_pk
+            primary key autoincrement,
+    song_id        integer not null
+        constraint loops_songs_id_fk
+            references songs
+            on update cascade on delete cascade,
+    name           TEXT    not null,
+    start_position integer not null,
+    end_position   integer not null
+);
+
+create index loops_song_id_index
+    on loops (song_id);
@@ -0,0 +1,197 @@
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+use anyhow::anyhow;
+use async_std::task;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use url::Url;
+use metadata_fetch::{ProposedRelease, ProposedTrack, ReleaseLookup};
+
+const USER_AGENT: &str = concat!("harborz/", env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/ravenblackdusk/harborz )");
+const INC: &str = "artist-credits recordings";
+const LOOKUPS: usize = 3;
+const SECTORS_PER_SECOND: f64 = 75.0;
+const PREGAP_SECTORS: u64 = 150;
+
+pub struct MusicBrainz {
+    base_uri: Url,
+    client: Client,
+    interval: Duration,
+    next_request: Mutex<Option<Instant>>,
+}
+
+impl Default for MusicBrainz {
+    fn default() -> Self {
+        Self::with_base_uri(Url::parse("https://musicbrainz.org").unwrap(), Duration::from_secs(1))
+    }
+}
+
+impl MusicBrainz {
+    pub fn with_base_uri(base_uri: Url, interval: Duration) -> Self {
+        Self {
+            base_uri,
+            client: Client::builder().user_agent(USER_AGENT).build().unwrap(),
+            interval,
+            next_request: Mutex::new(None),
+        }
+    }
+    // every lookup takes its turn, so that lookups running at the same time keep to the interval between them too
+    async fn throttle(&self) {
+        let wait = {
+            let mut next_request = self.next_request.lock().unwrap();
+            let now = Instant::now();
+            let start = next_request.map(|it| { it.max(now) }).unwrap_or(now);
+            *next_request = Some(start + self.interval);
+            start - now
+        };
+        if !wait.is_zero() { task::sleep(wait).await; }
+    }
+    async fn get<T: DeserializeOwned>(&self, uri: Url) -> anyhow::Result<T> {
+        self.throttle().await;
+        Ok(self.client.get(uri.as_str()).send().await?.error_for_status()?.json::<T>().await?)
+    }
+    async fn search(&self, artist: &str, album: &str) -> anyhow::Result<Vec<ProposedRelease>> {
+        let mut uri = self.base_uri.join("/ws/2/release/").unwrap();
+        uri.query_pairs_mut().append_pair("query", &query(artist, album)).append_pair("limit", &LOOKUPS.to_string())
+            .append_pair("fmt", "json");
+        let found = self.get::<Releases>(uri).await?.releases;
+        let mut releases = Vec::new();
+        let mut last_error = None;
+        for release in found {
+            let mut uri = self.base_uri.join(&format!("/ws/2/release/{}", release.id)).unwrap();
+            uri.query_pairs_mut().append_pair("inc", INC).append_pair("fmt", "json");
+            match self.get::<Release>(uri).await {
+                Ok(release) => { releases.push(self.propose(release)); }
+                Err(error) => { last_error = Some(error); }
+            }
+        }
+        match last_error {
+            Some(error) if releases.is_empty() => { Err(error) }
+            _ => { Ok(releases) }
+        }
+    }
+    async fn fuzzy_toc(&self, durations: &[Duration]) -> anyhow::Result<Vec<ProposedRelease>> {
+        let mut uri = self.base_uri.join("/ws/2/discid/-").unwrap();
+        uri.query_pairs_mut().append_pair("toc", &toc(durations)?).append_pair("cdstubs", "no")
+            .append_pair("inc", INC).append_pair("fmt", "json");
+        Ok(self.get::<Releases>(uri).await?.releases.into_iter().map(|release| { self.propose(release) }).collect())
+    }
+    fn propose(&self, release: Release) -> ProposedRelease {
+        ProposedRelease {
+            provider: self.name(),
+            artist: credit(&release.artist_credit),
+            year: release.date.and_then(|date| { date.get(..4).and_then(|year| { year.parse().ok() }) }),
+            tracks: release.media.into_iter().flat_map(|medium| {
+                let album_volume = medium.position;
+                medium.tracks.into_iter().map(move |track| {
+                    let artist_credit = if track.artist_credit.is_empty() {
+                        track.recording.map(|recording| { recording.artist_credit }).unwrap_or_default()
+                    } else {
+                        track.artist_credit
+                    };
+                    ProposedTrack {
+                        title: track.title,
+                        artist: Some(credit(&artist_credit)).filter(|it| { !it.is_empty() }),
+                        track_number: track.position,
+                        album_volume,
+                        duration: track.length.map(Duration::from_millis),
+                    }
+                })
+            }).collect(),
+            id: release.id,
+            album: release.title,
+        }
+    }
+}
+
+fn credit(artist_credit: &[ArtistCredit]) -> String {
+    artist_credit.iter().map(|credit| { format!("{}{}", credit.name, credit.joinphrase) }).collect()
+}
+
+fn escape(term: &str) -> String {
+    term.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn query(artist: &str, album: &str) -> String {
+    [("artist", artist), ("release", album)].into_iter().filter(|(_, term)| { !term.is_empty() })
+        .map(|(field, term)| { format!("{field}:\"{}\"", escape(term)) }).collect::<Vec<_>>().join(" AND ")
+}
+
+// as if the songs had been ripped from a CD in this order, which MusicBrainz matches fuzzily
+fn toc(durations: &[Duration]) -> anyhow::Result<String> {
+    if durations.is_empty() || durations.len() > 99 {
+        return Err(anyhow!("a disc has between 1 and 99 tracks, not [{}]", durations.len()));
+    }
+    let mut offsets = vec![PREGAP_SECTORS];
+    for duration in durations {
+        offsets.push(offsets.last().unwrap() + (duration.as_secs_f64() * SECTORS_PER_SECOND).round() as u64);
+    }
+    let lead_out = offsets.pop().unwrap();
+    Ok([1, durations.len() as u64, lead_out].into_iter().chain(offsets).map(|it| { it.to_string() })
+        .collect::<Vec<_>>().join(" "))
+}
+
+impl ReleaseLookup for MusicBrainz {
+    fn name(&self) -> &'static str {
+        "MusicBrainz"
+    }
+    fn lookup_release(&'static self, artist: &str, album: &str,
+        sender: Sender<anyhow::Result<Vec<ProposedRelease>>>) {
+        let (artist, album) = (artist.to_owned(), album.to_owned());
+        task::spawn(async move { sender.send(self.search(&artist, &album).await).unwrap(); });
+    }
+    fn lookup_release_by_durations(&'static self, durations: &[Duration],
+        sender: Sender<anyhow::Result<Vec<ProposedRelease>>>) {
+        let durations = durations.to_vec();
+        task::spawn(async move { sender.send(self.fuzzy_toc(&durations).await).unwrap(); });
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Releases {
+    releases: Vec<Release>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    id: String,
+    title: String,
+    date: Option<String>,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+    #[serde(default)]
+    media: Vec<Medium>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArtistCredit {
+    name: String,
+    #[serde(default)]
+    joinphrase: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Medium {
+    position: Option<i32>,
+    #[serde(default)]
+    tracks: Vec<Track>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Track {
+    position: Option<i32>,
+    title: String,
+    length: Option<u64>,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+    recording: Option<Recording>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Recording {
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+}
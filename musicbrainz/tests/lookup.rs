@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Response, Server};
+use url::Url;
+use metadata_fetch::{ProposedRelease, ReleaseLookup};
+use musicbrainz::MusicBrainz;
+
+fn serve(status: u16, interval: Duration) -> (&'static MusicBrainz, Arc<Mutex<Vec<String>>>) {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let base_uri = Url::parse(&format!("http://{}", server.server_addr().to_ip().unwrap())).unwrap();
+    let requested = Arc::new(Mutex::new(Vec::new()));
+    thread::spawn({
+        let requested = requested.clone();
+        move || {
+            for request in server.incoming_requests() {
+                requested.lock().unwrap().push(request.url().to_owned());
+                let path = request.url().split('?').next().unwrap().trim_end_matches('/').to_owned();
+                let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+                    .join(format!("{}.json", path.trim_start_matches('/')));
+                let response = match fs::read(fixture) {
+                    Ok(body) if status == 200 => { Response::from_data(body) }
+                    Ok(_) => { Response::from_string(r#"{"error":"Service unavailable"}"#).with_status_code(status) }
+                    Err(_) => { Response::from_string(r#"{"error":"Not Found"}"#).with_status_code(404) }
+                }.with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+                request.respond(response).unwrap();
+            }
+        }
+    });
+    (Box::leak(Box::new(MusicBrainz::with_base_uri(base_uri, interval))), requested)
+}
+
+fn query_pair(url: &str, key: &str) -> Option<String> {
+    Url::parse(&format!("http://localhost{url}")).unwrap().query_pairs()
+        .find(|(name, _)| { name == key }).map(|(_, value)| { value.into_owned() })
+}
+
+fn lookup_release(musicbrainz: &'static MusicBrainz, artist: &str, album: &str)
+    -> anyhow::Result<Vec<ProposedRelease>> {
+    let (sender, receiver) = channel();
+    musicbrainz.lookup_release(artist, album, sender);
+    receiver.recv_timeout(Duration::from_secs(10)).unwrap()
+}
+
+#[test]
+fn lookup_release_proposes_the_tags_of_each_match() {
+    let (musicbrainz, requested) = serve(200, Duration::ZERO);
+    let releases = lookup_release(musicbrainz, "The Beatles", "Abbey Road").unwrap();
+    assert_eq!(releases.len(), 2);
+    let vinyl = &releases[0];
+    assert_eq!(vinyl.provider, "MusicBrainz");
+    assert_eq!(vinyl.id, "7d1a8e3b-5f2c-4b6e-9a0d-3e4c5b6a7f81");
+    assert_eq!(vinyl.artist, "The Beatles");
+    assert_eq!(vinyl.album, "Abbey Road");
+    assert_eq!(vinyl.year, Some(1969));
+    assert_eq!(vinyl.tracks.len(), 17);
+    let here_comes_the_sun = &vinyl.tracks[6];
+    assert_eq!(here_comes_the_sun.title, "Here Comes the Sun");
+    assert_eq!(here_comes_the_sun.track_number, Some(1));
+    assert_eq!(here_comes_the_sun.album_volume, Some(2));
+    assert_eq!(here_comes_the_sun.duration, Some(Duration::from_millis(185733)));
+    assert_eq!(here_comes_the_sun.artist.as_deref(), Some("The Beatles"));
+    let cd = &releases[1];
+    assert_eq!(cd.year, Some(1987));
+    assert_eq!(cd.tracks[16].title, "Her Majesty");
+    assert_eq!(cd.tracks[16].track_number, Some(17));
+    assert_eq!(cd.tracks[16].album_volume, Some(1));
+    let requested = requested.lock().unwrap();
+    assert_eq!(query_pair(&requested[0], "query").unwrap(), r#"artist:"The Beatles" AND release:"Abbey Road""#);
+    assert_eq!(query_pair(&requested[1], "inc").unwrap(), "artist-credits recordings");
+}
+
+#[test]
+fn lookup_release_skips_a_release_that_fails() {
+    let (musicbrainz, requested) = serve(200, Duration::ZERO);
+    let releases = lookup_release(musicbrainz, "The Beatles", "Abbey Road").unwrap();
+    assert_eq!(releases.len(), 2);
+    assert_eq!(requested.lock().unwrap().len(), 4);
+}
+
+#[test]
+fn lookups_at_the_same_time_share_the_rate_limit() {
+    let interval = Duration::from_millis(100);
+    let (musicbrainz, requested) = serve(200, interval);
+    let start = Instant::now();
+    let (sender, receiver) = channel();
+    musicbrainz.lookup_release("The Beatles", "Abbey Road", sender.clone());
+    musicbrainz.lookup_release("The Beatles", "Abbey Road", sender);
+    for _ in 0..2 { receiver.recv_timeout(Duration::from_secs(10)).unwrap().unwrap(); }
+    assert_eq!(requested.lock().unwrap().len(), 8);
+    assert!(start.elapsed() >= interval * 7);
+}
+
+#[test]
+fn lookup_release_queries_only_the_given_terms() {
+    let (musicbrainz, requested) = serve(200, Duration::ZERO);
+    lookup_release(musicbrainz, "", r#"Abbey "Road""#).unwrap();
+    assert_eq!(query_pair(&requested.lock().unwrap()[0], "query").unwrap(), r#"release:"Abbey \"Road\"""#);
+}
+
+#[test]
+fn lookup_release_by_durations_sends_a_table_of_contents() {
+    let (musicbrainz, requested) = serve(200, Duration::ZERO);
+    let (sender, receiver) = channel();
+    musicbrainz.lookup_release_by_durations(&[Duration::from_secs(248), Duration::from_secs(218)], sender);
+    let releases = receiver.recv_timeout(Duration::from_secs(10)).unwrap().unwrap();
+    assert_eq!(releases.len(), 1);
+    assert_eq!(releases[0].artist, "Queen & David Bowie");
+    assert_eq!(releases[0].album, "Under Pressure");
+    assert_eq!(releases[0].year, Some(1981));
+    assert_eq!(releases[0].tracks.iter().map(|track| { track.title.as_str() }).collect::<Vec<_>>(),
+        vec!["Under Pressure", "Soul Brother"]);
+    assert_eq!(query_pair(&requested.lock().unwrap()[0], "toc").unwrap(), "1 2 35100 150 18750");
+}
+
+#[test]
+fn lookup_release_by_durations_needs_a_track() {
+    let (musicbrainz, requested) = serve(200, Duration::ZERO);
+    let (sender, receiver) = channel();
+    musicbrainz.lookup_release_by_durations(&[], sender);
+    assert!(receiver.recv_timeout(Duration::from_secs(10)).unwrap().is_err());
+    assert!(requested.lock().unwrap().is_empty());
+}
+
+#[test]
+fn lookup_release_fails_when_musicbrainz_is_unavailable() {
+    let (musicbrainz, _) = serve(503, Duration::ZERO);
+    assert!(lookup_release(musicbrainz, "The Beatles", "Abbey Road").is_err());
+}
@@ -1,47 +1,95 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::rc::Rc;
 use std::sync::Arc;
 use adw::NavigationPage;
 use adw::prelude::*;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, update};
-use diesel::dsl::{count_distinct, count_star, min};
-use gtk::{Image, Label, Separator};
+use diesel::{ExpressionMethods, NullableExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::dsl::{count_star, min};
+use gtk::{CheckButton, Image, Label, Separator};
 use gtk::Orientation::Vertical;
-use id3::TagLike;
 use crate::body::{ALBUM, ARTIST, Body, BodyType, handle_render, next_icon, SONG};
 use crate::body::download::albums::albums_page;
 use crate::body::merge::{KEY, add_menu_merge_button, MergeState};
 use crate::common::{FOLDER_MUSIC_ICON, ImagePathBuf, StyledLabelBuilder};
-use crate::common::constant::INSENSITIVE_FG;
+use crate::common::constant::{INSENSITIVE_FG, VARIOUS_ARTISTS};
 use crate::common::state::State;
 use crate::common::util::{or_none_arc, Plural};
+use crate::config::{Config, update_various_artists, VariousArtists};
 use crate::db::get_connection;
 use crate::schema::collections::dsl::collections;
 use crate::schema::collections::path;
-use crate::schema::songs::{album, artist, id, path as song_path};
+use crate::schema::albums;
+use crate::schema::config::dsl::config;
+use crate::schema::songs::{album_id, artist, path as song_path};
 use crate::schema::songs::dsl::songs;
-use crate::song::{join_path, WithImage};
+use crate::song::{join_path, primary_artist, WithImage};
+use crate::tag::Field;
 
 const HARBORZ: &'static str = "Harborz";
 
+fn artists() -> BTreeMap<Option<String>, (HashSet<Option<i32>>, i64, Option<(String, String)>)> {
+    let various_artists = config.get_result::<Config>(&mut get_connection()).unwrap().various_artists;
+    let album_artists = albums::table.select((albums::id, albums::artist))
+        .get_results::<(i32, Option<String>)>(&mut get_connection()).unwrap().into_iter().collect::<HashMap<_, _>>();
+    let mut artists = BTreeMap::<Option<String>, (HashSet<Option<i32>>, i64, Option<(String, String)>)>::new();
+    for (album_id_option, artist_string, count, collection_path, artist_song_path) in songs.inner_join(collections)
+        .group_by((album_id, artist)).select((album_id, artist, count_star(), min(path), min(song_path)))
+        .get_results::<(Option<i32>, Option<String>, i64, Option<String>, Option<String>)>(&mut get_connection())
+        .unwrap() {
+        let key = match album_id_option.map(|it| { album_artists.get(&it).cloned().flatten() }) {
+            Some(Some(album_artist_string)) if various_artists == VariousArtists::TrackArtists
+                && album_artist_string == VARIOUS_ARTISTS => {
+                artist_string.as_deref().map(primary_artist).map(String::from)
+            }
+            Some(album_artist_string) => { album_artist_string }
+            None => { artist_string }
+        };
+        let (album_ids, song_count, song_path_option) = artists.entry(key).or_default();
+        album_ids.insert(album_id_option);
+        *song_count += count;
+        if song_path_option.is_none() { *song_path_option = collection_path.zip(artist_song_path); }
+    }
+    artists
+}
+
 pub fn artists_page(state: Rc<State>) -> NavigationPage {
     let body = Body::new(HARBORZ, state.clone(), Some("Artists"), Vec::new(), BodyType::Artists);
-    let heading = add_menu_merge_button(ARTIST, &body.menu_button, &body.popover_box);
+    let heading = add_menu_merge_button(ARTIST, state.clone(), &body.menu_button, &body.popover_box);
+    let Config { various_artists, .. } = config.get_result::<Config>(&mut get_connection()).unwrap();
+    let group_compilations = CheckButton::builder().label("Group compilations")
+        .tooltip_text(format!("List compilations under {VARIOUS_ARTISTS} instead of under each track artist"))
+        .active(various_artists == VariousArtists::Grouped).build();
+    body.popover_box.append(&group_compilations);
+    group_compilations.connect_toggled({
+        let rerender = body.rerender.clone();
+        move |group_compilations| {
+            update_various_artists(if group_compilations.is_active() {
+                VariousArtists::Grouped
+            } else {
+                VariousArtists::TrackArtists
+            });
+            rerender.activate(None);
+        }
+    });
     let render = move || {
         let artists_box = gtk::Box::builder().orientation(Vertical).build();
-        let artists = songs.inner_join(collections).group_by(artist)
-            .select((artist, count_distinct(album), count_star(), min(path), min(song_path)))
-            .get_results::<(Option<String>, i64, i64, Option<String>, Option<String>)>(&mut get_connection()).unwrap();
+        let artists = artists();
         let subtitle = artists.len().number_plural(ARTIST);
         body.window_title.set_subtitle(&subtitle);
         let merge_state = MergeState::new(ARTIST, heading.clone(), Arc::new(String::from(HARBORZ)), Rc::new(subtitle),
             artists_box.clone(), &body.action_group, &body.header_bar, &body.menu_button,
-            |artists| { Box::new(artist.eq_any(artists)) }, || { Box::new(artist.is_null()) },
-            |tag, artist_string| { tag.set_artist(artist_string); }, |song, artist_string| {
-                update(songs.filter(id.eq(song.id))).set(artist.eq(Some(artist_string))).execute(&mut get_connection())
-                    .unwrap();
+            |artists| {
+                Box::new(album_id.eq_any(albums::table.filter(albums::artist.eq_any(artists))
+                    .select(albums::id.nullable())))
+            },
+            || {
+                Box::new(album_id.eq_any(albums::table.filter(albums::artist.is_null()).select(albums::id.nullable())))
             },
+            Field::AlbumArtist,
         );
-        for (artist_string, album_count, song_count, collection_path, artist_song_path) in artists {
+        for (artist_string, (album_ids, song_count, song_path_option)) in artists {
+            let album_count = album_ids.len();
+            let (collection_path, artist_song_path) = song_path_option.unwrap();
             let artist_string = artist_string.map(Arc::new);
             let artist_row = gtk::Box::builder().spacing(8).build();
             if let Some(artist_string) = artist_string.clone() {
@@ -49,7 +97,7 @@ pub fn artists_page(state: Rc<State>) -> NavigationPage {
             }
             artists_box.append(&artist_row);
             artists_box.append(&Separator::builder().build());
-            let logo = join_path(&collection_path.unwrap(), &artist_song_path.unwrap()).logo();
+            let logo = join_path(&collection_path, &artist_song_path).logo();
             artist_row.append(Image::builder().pixel_size(46).margin_start(8).build()
                 .set_or_default(&logo, FOLDER_MUSIC_ICON));
             merge_state.clone().handle_click(&artist_row, {
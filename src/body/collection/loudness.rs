@@ -0,0 +1,115 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, TryRecvError::*};
+use std::thread;
+use std::time::Duration;
+use adw::glib::{ControlFlow::*, timeout_add_local};
+use adw::prelude::*;
+use diesel::RunQueryDsl;
+use gtk::{Button, CheckButton, Label, ProgressBar};
+use gtk::Align::Center;
+use gtk::Orientation::{Horizontal, Vertical};
+use log::error;
+use crate::common::{gtk_box, StyledLabelBuilder};
+use crate::common::constant::INSENSITIVE_FG;
+use crate::common::util::Plural;
+use crate::config::{Config, update_loudness_analysis, update_write_replay_gain};
+use crate::db::get_connection;
+use crate::schema::config::dsl::config;
+use crate::song::loudness::{analyze_loudness, AnalysisProgress, pending_analysis};
+
+pub(super) fn loudness_box() -> gtk::Box {
+    let loudness_box = gtk::Box::builder().orientation(Vertical).margin_top(16).build();
+    let analysis_box = gtk_box(Horizontal);
+    loudness_box.append(&analysis_box);
+    let label_box = gtk::Box::builder().orientation(Vertical).hexpand(true).build();
+    analysis_box.append(&label_box);
+    label_box.append(&Label::builder().label("Loudness analysis").xalign(0.0).margin_ellipsized(4).build());
+    let pending_label = Label::builder().xalign(0.0).margin_ellipsized(4).subscript().name(INSENSITIVE_FG).build();
+    label_box.append(&pending_label);
+    let refresh_pending = Rc::new(move || {
+        pending_label.set_label(&format!("{} without ReplayGain", pending_analysis(&mut get_connection())
+            .number_plural("song")));
+    });
+    refresh_pending();
+    let analyze_button = Button::builder().label("Analyze").valign(Center).build();
+    analysis_box.append(&analyze_button);
+    let Config { loudness_analysis, write_replay_gain, .. }
+        = config.get_result::<Config>(&mut get_connection()).unwrap();
+    let write_tags = CheckButton::builder().label("Write ReplayGain tags")
+        .tooltip_text("Also save the analysed gain and peak to the files").active(write_replay_gain == 1).build();
+    loudness_box.append(&write_tags);
+    write_tags.connect_toggled(|write_tags| { update_write_replay_gain(write_tags.is_active()); });
+    let progress_box = gtk::Box::builder().visible(false).build();
+    loudness_box.append(&progress_box);
+    let progress_bar = ProgressBar::builder().hexpand(true).valign(Center).show_text(true).build();
+    progress_box.append(&progress_bar);
+    let cancel_button = Button::builder().icon_name("process-stop").tooltip_text("Cancel analysis").build();
+    progress_box.append(&cancel_button);
+    let cancel = Rc::new(RefCell::new(None::<Arc<AtomicBool>>));
+    cancel_button.connect_clicked({
+        let cancel = cancel.clone();
+        move |cancel_button| {
+            if let Some(cancel) = cancel.borrow().as_ref() { cancel.store(true, Ordering::Relaxed); }
+            cancel_button.set_sensitive(false);
+        }
+    });
+    let start = {
+        let analyze_button = analyze_button.clone();
+        move || {
+            analyze_button.set_sensitive(false);
+            update_loudness_analysis(true);
+            let (sender, receiver) = channel::<AnalysisProgress>();
+            let write_tags = write_tags.is_active();
+            thread::spawn(move || {
+                if let Err(error) = analyze_loudness(write_tags, sender, &mut get_connection()) {
+                    error!("error analysing loudness [{error}]");
+                }
+            });
+            let failed = Cell::new(0);
+            timeout_add_local(Duration::from_millis(500), {
+                let analyze_button = analyze_button.clone();
+                let progress_box = progress_box.clone();
+                let progress_bar = progress_bar.clone();
+                let cancel_button = cancel_button.clone();
+                let cancel = cancel.clone();
+                let refresh_pending = refresh_pending.clone();
+                move || {
+                    let mut last_fraction = None;
+                    loop {
+                        match receiver.try_recv() {
+                            Err(Empty) => { break; }
+                            Err(Disconnected) => {
+                                progress_box.set_visible(false);
+                                analyze_button.set_sensitive(true);
+                                return Break;
+                            }
+                            Ok(AnalysisProgress::Start(analysis_cancel)) => {
+                                progress_bar.set_fraction(0.0);
+                                progress_bar.set_text(None);
+                                *cancel.borrow_mut() = Some(analysis_cancel);
+                                cancel_button.set_sensitive(true);
+                                progress_box.set_visible(true);
+                            }
+                            Ok(AnalysisProgress::Fraction(fraction)) => { last_fraction = Some(fraction); }
+                            Ok(AnalysisProgress::Failed(_, _)) => { failed.set(failed.get() + 1); }
+                            Ok(AnalysisProgress::End) => {
+                                progress_box.set_visible(false);
+                                refresh_pending();
+                                last_fraction = None;
+                            }
+                        }
+                    }
+                    if let Some(fraction) = last_fraction { progress_bar.set_fraction(fraction); }
+                    if failed.get() > 0 { progress_bar.set_text(Some(&format!("{} failed", failed.get()))); }
+                    Continue
+                }
+            });
+        }
+    };
+    if loudness_analysis == 1 { start(); }
+    analyze_button.connect_clicked(move |_| { start(); });
+    loudness_box
+}
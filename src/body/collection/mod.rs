@@ -1,5 +1,7 @@
+use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Sender, TryRecvError};
 use std::time::Duration;
 use TryRecvError::{Disconnected, Empty};
@@ -8,59 +10,155 @@ use adw::glib::{ControlFlow::*, timeout_add_local};
 use adw::NavigationPage;
 use adw::prelude::*;
 use async_std::task;
-use diesel::{delete, ExpressionMethods, insert_or_ignore_into, QueryDsl, RunQueryDsl};
+use diesel::{delete, ExpressionMethods, insert_or_ignore_into, QueryDsl, RunQueryDsl, update};
 use diesel::prelude::*;
 use diesel::result::Error;
-use gtk::{Button, FileDialog, Label, ProgressBar};
+use gtk::{Button, Expander, FileDialog, Label, ProgressBar};
+use gtk::Align::Center;
 use gtk::Orientation::{Horizontal, Vertical};
 use log::error;
-use crate::body::collection::model::Collection;
+use crate::body::collection::loudness::loudness_box;
+use crate::body::collection::model::{Collection, ImportIssue};
 use crate::body::{action_name, RERENDER};
 use crate::common::{gtk_box, StyledLabelBuilder, StyledWidget};
-use crate::common::constant::DESTRUCTIVE_ACTION;
+use crate::common::constant::{DESTRUCTIVE_ACTION, INSENSITIVE_FG};
 use crate::common::state::State;
-use crate::common::util::PathString;
+use crate::common::util::{PathString, Plural};
 use crate::db::get_connection;
 use crate::schema::bodies::dsl::bodies;
 use crate::schema::collections::dsl::collections;
 use crate::schema::collections::path;
-use crate::song::{import_songs, ImportProgress};
+use crate::schema::import_issues::{collection_id, ignored, path as issue_path};
+use crate::schema::import_issues::dsl::import_issues;
+use crate::song::{import_song, import_songs, ImportProgress, join_path};
+use crate::song::watch::{unwatch, watch};
 
 pub mod model;
 pub mod button;
 pub mod page;
+mod loudness;
 
-fn handle_progress<F: Fn(Arc<RwLock<Collection>>) + 'static>(collections_box: &gtk::Box, on_collection_end: F)
-    -> Sender<ImportProgress> {
+fn handle_progress<F: Fn(Arc<RwLock<Collection>>) + 'static>(collections_box: Option<&gtk::Box>,
+    on_collection_end: F) -> Sender<ImportProgress> {
     let (sender, receiver) = channel::<ImportProgress>();
-    let progress_bar = ProgressBar::builder().hexpand(true).build();
+    let progress_box = gtk_box(Horizontal);
+    let progress_bar = ProgressBar::builder().hexpand(true).valign(Center).show_text(true).build();
+    progress_box.append(&progress_bar);
+    let cancel_button = Button::builder().icon_name("process-stop").tooltip_text("Cancel import").build();
+    progress_box.append(&cancel_button);
+    let cancel = Rc::new(RefCell::new(None::<Arc<AtomicBool>>));
+    cancel_button.connect_clicked({
+        let cancel = cancel.clone();
+        move |cancel_button| {
+            if let Some(cancel) = cancel.borrow().as_ref() { cancel.store(true, Ordering::Relaxed); }
+            cancel_button.set_sensitive(false);
+        }
+    });
+    let (mut skipped, mut failed) = (0, 0);
     timeout_add_local(Duration::from_millis(500), {
-        let collections_box = collections_box.clone();
+        let collections_box = collections_box.cloned();
         move || {
             let mut last_fraction = None;
             loop {
                 match receiver.try_recv() {
                     Err(Empty) => { break; }
                     Err(Disconnected) => { return Break; }
-                    Ok(ImportProgress::CollectionStart) => {
+                    Ok(ImportProgress::CollectionStart(import_cancel)) => {
+                        (skipped, failed) = (0, 0);
                         progress_bar.set_fraction(0.0);
-                        collections_box.append(&progress_bar);
+                        progress_bar.set_text(None);
+                        *cancel.borrow_mut() = Some(import_cancel);
+                        cancel_button.set_sensitive(true);
+                        if let Some(collections_box) = &collections_box { collections_box.append(&progress_box); }
                     }
                     Ok(ImportProgress::CollectionEnd(collection)) => {
-                        collections_box.remove(&progress_bar);
+                        if let Some(collections_box) = &collections_box { collections_box.remove(&progress_box); }
                         on_collection_end(collection);
                         last_fraction = None;
                     }
                     Ok(ImportProgress::Fraction(fraction)) => { last_fraction = Some(fraction); }
+                    Ok(ImportProgress::Skipped(_)) => { skipped += 1; }
+                    Ok(ImportProgress::Failed(_, _)) => { failed += 1; }
                 }
             }
             if let Some(fraction) = last_fraction { progress_bar.set_fraction(fraction); }
+            if skipped + failed > 0 { progress_bar.set_text(Some(&format!("{skipped} skipped, {failed} failed"))); }
             Continue
         }
     });
     sender
 }
 
+fn watch_collection(collection: Arc<RwLock<Collection>>, state: Rc<State>) {
+    let sender = handle_progress(None, move |_| {
+        if let Some(navigation_page) = state.navigation_view.visible_page() {
+            navigation_page.activate_action(&action_name(RERENDER), None).ok();
+        }
+    });
+    if let Err(error) = watch(collection.clone(), sender) {
+        error!("error watching collection [{:?}] [{error}]", collection.read().unwrap());
+    }
+}
+
+pub fn watch_collections(state: Rc<State>) {
+    for collection in collections.load::<Collection>(&mut get_connection()).unwrap() {
+        watch_collection(Arc::new(RwLock::new(collection)), state.clone());
+    }
+}
+
+fn render_issues(issues_expander: &Expander, collections_box: &gtk::Box, collection: &Arc<RwLock<Collection>>) {
+    let issues = import_issues.filter(collection_id.eq(collection.read().unwrap().id)).filter(ignored.eq(0))
+        .order_by(issue_path).get_results::<ImportIssue>(&mut get_connection()).unwrap();
+    issues_expander.set_visible(!issues.is_empty());
+    issues_expander.set_label(Some(&issues.len().number_plural("Import issue")));
+    let issues_box = gtk_box(Vertical);
+    for issue in issues {
+        let issue_box = gtk_box(Horizontal);
+        issues_box.append(&issue_box);
+        let label_box = gtk::Box::builder().orientation(Vertical).hexpand(true).build();
+        issue_box.append(&label_box);
+        label_box.append(&Label::builder().label(&issue.path).xalign(0.0).ellipsized().build());
+        label_box.append(&Label::builder().label(&issue.reason).xalign(0.0).ellipsized().subscript()
+            .name(INSENSITIVE_FG).build());
+        let retry_button = Button::builder().icon_name("view-refresh").tooltip_text("Retry").build();
+        issue_box.append(&retry_button);
+        retry_button.connect_clicked({
+            let issues_expander = issues_expander.clone();
+            let collections_box = collections_box.clone();
+            let collection = collection.clone();
+            let entry_path = join_path(&collection.read().unwrap().path, &issue.path);
+            move |_| {
+                let sender = handle_progress(Some(&collections_box), {
+                    let issues_expander = issues_expander.clone();
+                    let collections_box = collections_box.clone();
+                    move |collection| { render_issues(&issues_expander, &collections_box, &collection); }
+                });
+                task::spawn({
+                    let collection = collection.clone();
+                    let entry_path = entry_path.clone();
+                    async move {
+                        if let Err(error) = import_song(entry_path.clone(), collection, sender, &mut get_connection()) {
+                            error!("error retrying import of [{entry_path:?}] [{error}]");
+                        }
+                    }
+                });
+            }
+        });
+        let ignore_button = Button::builder().icon_name("list-remove").tooltip_text("Ignore").build();
+        issue_box.append(&ignore_button);
+        ignore_button.connect_clicked({
+            let issues_expander = issues_expander.clone();
+            let collections_box = collections_box.clone();
+            let collection = collection.clone();
+            move |_| {
+                update(import_issues.find(issue.id)).set(ignored.eq(1)).execute(&mut get_connection()).unwrap();
+                render_issues(&issues_expander, &collections_box, &collection);
+            }
+        });
+    }
+    issues_expander.set_child(Some(&issues_box));
+}
+
 fn add(collections_box: &gtk::Box, collection: Arc<RwLock<Collection>>, state: Rc<State>) {
     let collection_box = gtk_box(Horizontal);
     collections_box.append(&collection_box);
@@ -68,18 +166,27 @@ fn add(collections_box: &gtk::Box, collection: Arc<RwLock<Collection>>, state: R
         .label(collection.read().unwrap().path.to_path().file_name().unwrap().to_str().unwrap()).margin_ellipsized(4)
         .build()
     );
+    let issues_expander = Expander::builder().margin_start(8).margin_end(8).build();
+    collections_box.append(&issues_expander);
+    render_issues(&issues_expander, collections_box, &collection);
     let sync_button = Button::builder().icon_name("view-refresh").build();
     collection_box.append(&sync_button);
     let id = collection.read().unwrap().id;
     sync_button.connect_clicked({
         let collections_box = collections_box.clone();
+        let issues_expander = issues_expander.clone();
         move |_| {
-            let sender = handle_progress(&collections_box, |_| {});
+            let sender = handle_progress(Some(&collections_box), {
+                let collections_box = collections_box.clone();
+                let issues_expander = issues_expander.clone();
+                move |collection| { render_issues(&issues_expander, &collections_box, &collection); }
+            });
             task::spawn({
                 let collection = collection.clone();
                 async move {
-                    get_connection().transaction(|connection| import_songs(collection, sender, connection))
-                        .unwrap();
+                    if let Err(error) = import_songs(collection.clone(), sender, &mut get_connection()) {
+                        error!("error importing collection [{:?}] [{error}]", collection.read().unwrap());
+                    }
                 }
             });
         }
@@ -89,6 +196,7 @@ fn add(collections_box: &gtk::Box, collection: Arc<RwLock<Collection>>, state: R
     remove_button.connect_clicked({
         let collections_box = collections_box.clone();
         move |_| {
+            unwatch(id);
             delete(collections.find(id)).execute(&mut get_connection()).unwrap();
             let pages = state.navigation_view.navigation_stack();
             for navigation_page in pages.iter::<NavigationPage>().take((pages.n_items() - 1) as usize) {
@@ -96,6 +204,7 @@ fn add(collections_box: &gtk::Box, collection: Arc<RwLock<Collection>>, state: R
                 navigation_page.unwrap().activate_action(&action_name(RERENDER), None).unwrap();
             }
             collections_box.remove(&collection_box);
+            collections_box.remove(&issues_expander);
         }
     });
 }
@@ -109,6 +218,7 @@ fn add_collection_box(state: Rc<State>) -> gtk::Box {
     }
     let browse_button = Button::builder().label("Browse").build().suggested_action();
     add_collection_box.append(&browse_button);
+    add_collection_box.append(&loudness_box());
     browse_button.connect_clicked({
         move |_| {
             FileDialog::builder().title("Collection directories").accept_label("Choose").build()
@@ -123,24 +233,29 @@ fn add_collection_box(state: Rc<State>) -> gtk::Box {
                                     let paths = files.into_iter()
                                         .map(|file| { file.unwrap().downcast::<File>().unwrap().path().unwrap() })
                                         .collect::<Vec<_>>();
-                                    let sender = handle_progress(&collections_box, {
+                                    let sender = handle_progress(Some(&collections_box), {
                                         let collections_box = collections_box.clone();
-                                        move |collection| { add(&collections_box, collection, state.clone()); }
+                                        move |collection| {
+                                            add(&collections_box, collection.clone(), state.clone());
+                                            watch_collection(collection, state.clone());
+                                        }
                                     });
                                     task::spawn(async move {
                                         for path_buf in paths {
-                                            get_connection().transaction(|connection| {
-                                                anyhow::Ok(match insert_or_ignore_into(collections)
-                                                    .values(path.eq(path_buf.to_str().unwrap()))
-                                                    .get_result::<Collection>(connection) {
-                                                    Err(Error::NotFound) => {}
-                                                    Ok(collection) => {
-                                                        import_songs(Arc::new(RwLock::new(collection)), sender.clone(),
-                                                            connection)?
+                                            match insert_or_ignore_into(collections)
+                                                .values(path.eq(path_buf.to_str().unwrap()))
+                                                .get_result::<Collection>(&mut get_connection()) {
+                                                Err(Error::NotFound) => {}
+                                                Ok(collection) => {
+                                                    if let Err(error) = import_songs(Arc::new(RwLock::new(collection)),
+                                                        sender.clone(), &mut get_connection()) {
+                                                        error!("error importing collection [{path_buf:?}] [{error}]");
                                                     }
-                                                    result => { result?; }
-                                                })
-                                            }).unwrap();
+                                                }
+                                                Err(error) => {
+                                                    error!("error adding collection [{path_buf:?}] [{error}]");
+                                                }
+                                            }
                                         }
                                     });
                                 }
@@ -6,3 +6,14 @@ pub struct Collection {
     pub path: String,
     pub modified: Option<i64>,
 }
+
+#[derive(diesel::Queryable, diesel::Selectable, Debug)]
+#[diesel(table_name = crate::schema::import_issues)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ImportIssue {
+    pub id: i32,
+    pub collection_id: i32,
+    pub path: String,
+    pub reason: String,
+    pub ignored: i32,
+}
@@ -1,30 +1,37 @@
+use std::collections::HashMap;
 use std::path::Path;
 use std::rc::Rc;
 use std::sync::Arc;
 use adw::NavigationPage;
 use adw::prelude::*;
 use bytes::Bytes;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, update};
+use diesel::{BoolExpressionMethods, ExpressionMethods, NullableExpressionMethods, QueryDsl, RunQueryDsl};
 use diesel::dsl::{count_star, max, min};
+use diesel::expression_methods::SqliteExpressionMethods;
 use gtk::{Image, Label, Separator};
 use gtk::Align::Center;
 use gtk::Orientation::Vertical;
-use id3::TagLike;
-use metadata_fetch::{ArtistSearch, DownloadArtistEvent::*, MetadataFetcher};
-use crate::body::{ALBUM, Body, BodyType, handle_render, next_icon, SONG};
-use crate::body::download::{append_download_button, handle_scroll, METAL_ARCHIVES, save};
+use metadata_fetch::{ArtistSearch, DownloadArtistEvent::*};
+use crate::body::{ALBUM, append_queue_buttons, Body, BodyType, handle_render, next_icon, SONG};
+use crate::body::download::{append_download_button, handle_scroll, save};
+use crate::body::download::provider::registry;
 use crate::body::download::songs::songs_page;
-use crate::body::merge::{KEY, add_menu_merge_button, MergeState};
+use crate::body::facet::Facet;
+use crate::body::merge::{KEY, add_menu_merge_button, MergeState, Query};
 use crate::common::{FOLDER_MUSIC_ICON, ImagePathBuf, StyledLabelBuilder};
-use crate::common::constant::INSENSITIVE_FG;
+use crate::common::constant::{INSENSITIVE_FG, VARIOUS_ARTISTS};
 use crate::common::state::State;
 use crate::common::util::{or_none_arc, Plural};
+use crate::config::{Config, VariousArtists};
 use crate::db::get_connection;
 use crate::schema::collections::dsl::collections;
 use crate::schema::collections::path;
-use crate::schema::songs::{album, artist, id, path as song_path, year};
+use crate::schema::albums;
+use crate::schema::config::dsl::config;
+use crate::schema::songs::{album, album_id, album_volume, artist, id, track_number, year};
 use crate::schema::songs::dsl::songs;
-use crate::song::{join_path, WithImage};
+use crate::song::{Album, join_path, primary_artist, WithImage};
+use crate::tag::Field;
 
 fn save_option(image_path: impl AsRef<Path>, bytes: Option<Bytes>) {
     if let Some(bytes) = bytes {
@@ -32,23 +39,59 @@ fn save_option(image_path: impl AsRef<Path>, bytes: Option<Bytes>) {
     }
 }
 
+fn artist_filter(artist_string: &Option<Arc<String>>) -> Query {
+    let artist_str = artist_string.as_ref().map(|it| { it.as_str() });
+    let album_ids = |album_artist_str: Option<&str>| {
+        albums::table.filter(albums::artist.is(album_artist_str.map(String::from))).select(albums::id.nullable())
+    };
+    if config.get_result::<Config>(&mut get_connection()).unwrap().various_artists == VariousArtists::TrackArtists {
+        // compilation tracks are listed under their primary artist, as on the artists page
+        let track_ids = songs.filter(album_id.eq_any(album_ids(Some(VARIOUS_ARTISTS)))).select((id, artist))
+            .get_results::<(i32, Option<String>)>(&mut get_connection()).unwrap().into_iter()
+            .filter(|(_, track_artist)| { track_artist.as_deref().map(primary_artist) == artist_str })
+            .map(|(song_id, _)| { song_id }).collect::<Vec<_>>();
+        Box::new(album_id.eq_any(album_ids(artist_str)).or(id.eq_any(track_ids)))
+    } else {
+        Box::new(album_id.eq_any(album_ids(artist_str)))
+    }
+}
+
 fn image_box(gtk_box: &gtk::Box) -> gtk::Box {
     gtk_box.first_child().and_downcast::<gtk::Box>().unwrap()
 }
 
 pub fn albums_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_adjustment: Option<f64>)
     -> NavigationPage {
-    let (artist_string, logo_or_photo) = {
+    let (facet, artist_string, logo_or_photo) = {
         let mut params = params.clone();
-        (params.pop().unwrap(), params.pop().unwrap())
+        let facet = if params.len() == 4 {
+            let value = params.pop().unwrap();
+            params.pop().unwrap().and_then(|it| { Facet::from_param(&it) }).map(|facet| { (facet, value) })
+        } else {
+            None
+        };
+        (facet, params.pop().unwrap(), params.pop().unwrap())
     };
-    let title = or_none_arc(artist_string.clone());
+    let title = or_none_arc(if let Some((_, value)) = &facet { value.clone() } else { artist_string.clone() });
     let body = Body::new(&*title, state.clone(), None, params, BodyType::Albums);
-    let heading = add_menu_merge_button(ALBUM, &body.menu_button, &body.popover_box);
+    let heading = add_menu_merge_button(ALBUM, state.clone(), &body.menu_button, &body.popover_box);
+    if let Some((facet, value)) = facet.clone() {
+        append_queue_buttons(Some(facet.param()), &body.popover_box, &body.menu_button,
+            move || { facet.song_ids(&value) });
+    } else {
+        append_queue_buttons(Some("artist"), &body.popover_box, &body.menu_button, {
+            let artist_string = artist_string.clone();
+            move || {
+                songs.inner_join(collections).filter(artist_filter(&artist_string))
+                    .order_by((year, album, album_volume, track_number, id)).select(id)
+                    .get_results::<i32>(&mut get_connection()).unwrap()
+            }
+        });
+    }
     if let Some(artist_string) = artist_string.clone() {
         append_download_button("logo & photo", &body.popover_box, {
             let artist_string = artist_string.clone();
-            move |sender| { METAL_ARCHIVES.download_artist_logo_and_photo(&artist_string, sender); }
+            move |sender| { registry().download_artist_logo_and_photo(&artist_string, sender); }
         }, 2, move |search_result, handle_search_result, handle_bytes| {
             match search_result {
                 SearchResult(search_result) => { handle_search_result(search_result); }
@@ -59,7 +102,7 @@ pub fn albums_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_ad
                     handle_bytes(i, photo, Box::new(|gtk_box, image| { image_box(gtk_box).append(image); }), 1);
                 }
             }
-        }, |ArtistSearch { name, genre, location }| {
+        }, |ArtistSearch { provider, name, genre, location }| {
             let gtk_box = gtk::Box::builder().orientation(Vertical).spacing(4).hexpand(true).margin_start(4)
                 .build();
             let image_box = gtk::Box::builder().spacing(4).halign(Center).build();
@@ -68,6 +111,7 @@ pub fn albums_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_ad
             gtk_box.append(&Label::builder().label(&genre).wrap(true).build());
             gtk_box.append(&Label::builder().label(&location).wrap(true).subscript().name(INSENSITIVE_FG)
                 .build());
+            gtk_box.append(&Label::builder().label(provider).subscript().name(INSENSITIVE_FG).build());
             gtk_box
         }, {
             let logo_or_photo = logo_or_photo.clone().unwrap();
@@ -79,49 +123,60 @@ pub fn albums_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_ad
     }
     let adjustment = body.scrolled_window.vadjustment();
     let render = move || {
-        let statement = songs.inner_join(collections).group_by(album).order_by(min(year).desc())
-            .select((album, count_star(), min(path), min(song_path), min(year), max(year))).into_boxed();
-        let albums = if let Some(artist_string) = &artist_string {
-            statement.filter(artist.eq(artist_string.as_ref()))
+        let statement = songs.inner_join(collections).group_by(album_id).order_by(min(year).desc())
+            .select((album_id, count_star(), min(path), min(year), max(year))).into_boxed();
+        let grouped = if let Some((facet, value)) = &facet {
+            statement.filter(facet.filter(value))
         } else {
-            statement.filter(artist.is_null())
-        }.get_results::<(Option<String>, i64, Option<String>, Option<String>, Option<i32>, Option<i32>)>(&mut get_connection())
+            statement.filter(artist_filter(&artist_string))
+        }.get_results::<(Option<i32>, i64, Option<String>, Option<i32>, Option<i32>)>(&mut get_connection())
             .unwrap();
+        let mut album_models = albums::table.filter(albums::id.eq_any(grouped.iter().filter_map(|(it, ..)| { *it })
+            .collect::<Vec<_>>())).get_results::<Album>(&mut get_connection()).unwrap().into_iter()
+            .map(|album_model| { (album_model.id, album_model) }).collect::<HashMap<_, _>>();
+        let albums = grouped.into_iter().filter_map(|(album_id_option, count, collection_path, min_year, max_year)| {
+            album_id_option.and_then(|it| { album_models.remove(&it) })
+                .map(|album_model| { (album_model, count, collection_path, min_year, max_year) })
+        }).collect::<Vec<_>>();
         let subtitle = albums.len().number_plural(ALBUM);
         body.window_title.set_subtitle(&subtitle);
         let albums_box = gtk::Box::builder().orientation(Vertical).build();
         let merge_state = MergeState::new(ALBUM, heading.clone(), title.clone(), Rc::new(subtitle),
             albums_box.clone(), &body.action_group, &body.header_bar, &body.menu_button,
             |albums| { Box::new(album.eq_any(albums)) }, || { Box::new(album.is_null()) },
-            |tag, album_string| { tag.set_album(album_string); }, |song, album_string| {
-                update(songs.filter(id.eq(song.id))).set(album.eq(Some(album_string))).execute(&mut get_connection())
-                    .unwrap();
-            },
+            Field::Album,
         );
-        for (album_string, count, collection_path, album_song_path, min_year, max_year) in albums {
-            let album_string = album_string.map(Arc::new);
+        for (album_model, count, collection_path, min_year, max_year) in albums {
+            let album_artist = album_model.artist.map(Arc::new);
+            let album_string = album_model.title.map(Arc::new);
+            let album_id = album_model.id;
+            let cover = join_path(&collection_path.unwrap(), &album_model.cover);
             let album_row = gtk::Box::builder().spacing(8).build();
             if let Some(album_string) = album_string.clone() {
                 unsafe { album_row.set_data(KEY, album_string); }
             }
             albums_box.append(&album_row);
             albums_box.append(&Separator::builder().build());
-            let cover = join_path(&collection_path.unwrap(), &album_song_path.unwrap()).cover();
             album_row.append(Image::builder().pixel_size(46).margin_start(8).build()
                 .set_or_default(&cover, FOLDER_MUSIC_ICON));
             merge_state.clone().handle_click(&album_row, {
                 let album_string = album_string.clone();
-                let artist_string = artist_string.clone();
+                let album_artist = album_artist.clone();
                 let state = state.clone();
                 move || {
                     state.navigation_view.push(&songs_page(vec![cover.to_str().map(|it| { Arc::new(it.to_owned()) }),
-                        artist_string.clone(), album_string.clone()], state.clone(), None));
+                        album_artist.clone(), album_string.clone(), Some(Arc::new(album_id.to_string()))],
+                        state.clone(), None));
                 }
             });
             let album_box = gtk::Box::builder().orientation(Vertical)
                 .margin_start(8).margin_end(4).margin_top(12).margin_bottom(12).build();
             album_row.append(&album_box);
             album_box.append(&Label::builder().label(&*or_none_arc(album_string)).ellipsized().build());
+            if facet.is_some() || album_artist != artist_string {
+                album_box.append(&Label::builder().label(&*or_none_arc(album_artist)).name(INSENSITIVE_FG)
+                    .ellipsized().subscript().build());
+            }
             let year_builder = Label::builder().name(INSENSITIVE_FG).ellipsized().subscript();
             let count_box = gtk::Box::builder().spacing(4).name(INSENSITIVE_FG).build();
             count_box.append(&Label::builder().label(&count.to_string()).subscript().build());
@@ -0,0 +1,112 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use bytes::Bytes;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use log::error;
+use metadata_fetch::{AlbumSearch, ArtistSearch, DownloadAlbumEvent, DownloadArtistEvent, MetadataFetcher};
+use crate::db::get_connection;
+use crate::schema::collections::dsl::collections;
+use crate::schema::collections::path as collection_path;
+use crate::schema::songs::{album, artist, path};
+use crate::schema::songs::dsl::songs;
+use crate::song::{join_path, WithImage};
+
+const IMAGE_EXTENSIONS: [&'static str; 5] = ["jpg", "jpeg", "png", "webp", "bmp"];
+
+pub struct LocalFolder;
+
+fn song_path((collection_path_string, song_path_string): (String, String)) -> PathBuf {
+    join_path(&collection_path_string, &song_path_string)
+}
+
+fn images(directory: &Path) -> Vec<PathBuf> {
+    match fs::read_dir(directory) {
+        Ok(read_dir) => {
+            let mut images = read_dir.filter_map(|entry| { entry.ok() }).map(|entry| { entry.path() })
+                .filter(|image_path| {
+                    image_path.is_file() && image_path.extension().and_then(|it| { it.to_str() })
+                        .map(|extension| { IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()) })
+                        .unwrap_or(false)
+                }).collect::<Vec<_>>();
+            images.sort();
+            images
+        }
+        Err(error) => {
+            error!("error reading directory [{directory:?}] [{error}]");
+            Vec::new()
+        }
+    }
+}
+
+fn file_name(image_path: &Path) -> String {
+    image_path.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+fn read(image_path: &Path) -> anyhow::Result<Bytes> {
+    Ok(Bytes::from(fs::read(image_path)?))
+}
+
+fn named(images: &Vec<PathBuf>, names: &[&str]) -> Option<PathBuf> {
+    images.iter().find(|image_path| {
+        let stem = image_path.file_stem().unwrap().to_string_lossy().to_lowercase();
+        names.iter().any(|name| { stem.contains(name) })
+    }).cloned()
+}
+
+impl MetadataFetcher for LocalFolder {
+    fn name(&self) -> &'static str {
+        "Local folder"
+    }
+    fn download_artist_logo_and_photo(&'static self, artist_str: &str, sender: Sender<DownloadArtistEvent>) {
+        let artist_string = artist_str.to_owned();
+        thread::spawn(move || {
+            let directories = songs.inner_join(collections).filter(artist.eq(&artist_string))
+                .select((collection_path, path)).get_results::<(String, String)>(&mut get_connection()).unwrap()
+                .into_iter().filter_map(|row| { song_path(row).logo().parent().map(Path::to_path_buf) })
+                .collect::<BTreeSet<_>>();
+            let found = directories.into_iter().filter_map(|directory| {
+                let images = images(&directory);
+                let logo = named(&images, &["logo"]);
+                let photo = named(&images, &["photo", "artist", "band"]);
+                if logo.is_none() && photo.is_none() { None } else { Some((directory, logo, photo)) }
+            }).collect::<Vec<_>>();
+            sender.send(DownloadArtistEvent::SearchResult(Ok(found.iter().map(|(directory, logo, photo)| {
+                Ok(ArtistSearch {
+                    provider: self.name(),
+                    name: artist_string.clone(),
+                    genre: logo.iter().chain(photo).map(|it| { file_name(it) }).collect::<Vec<_>>().join(", "),
+                    location: directory.to_string_lossy().into_owned(),
+                })
+            }).collect()))).unwrap();
+            for (i, (_, logo, photo)) in found.into_iter().enumerate() {
+                if let Some(logo) = logo { sender.send(DownloadArtistEvent::Logo(i, read(&logo))).unwrap(); }
+                if let Some(photo) = photo { sender.send(DownloadArtistEvent::Photo(i, read(&photo))).unwrap(); }
+            }
+        });
+    }
+    fn download_cover(&'static self, artist_str: &str, album_str: &str, sender: Sender<DownloadAlbumEvent>) {
+        let (artist_string, album_string) = (artist_str.to_owned(), album_str.to_owned());
+        thread::spawn(move || {
+            let directories = songs.inner_join(collections).filter(artist.eq(&artist_string))
+                .filter(album.eq(&album_string)).select((collection_path, path))
+                .get_results::<(String, String)>(&mut get_connection()).unwrap()
+                .into_iter().filter_map(|row| { song_path(row).cover().parent().map(Path::to_path_buf) })
+                .collect::<BTreeSet<_>>();
+            let found = directories.iter().flat_map(|directory| { images(directory) }).collect::<Vec<_>>();
+            sender.send(DownloadAlbumEvent::SearchResult(Ok(found.iter().map(|image_path| {
+                Ok(AlbumSearch {
+                    provider: self.name(),
+                    artist: artist_string.clone(),
+                    album: album_string.clone(),
+                    album_type: file_name(image_path),
+                })
+            }).collect()))).unwrap();
+            for (i, image_path) in found.iter().enumerate() {
+                sender.send(DownloadAlbumEvent::Cover(i, read(image_path))).unwrap();
+            }
+        });
+    }
+}
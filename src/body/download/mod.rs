@@ -14,16 +14,15 @@ use adw::Window;
 use bytes::{Buf, Bytes};
 use gtk::{Adjustment, Button, Image, MenuButton, Overlay};
 use log::{error, warn};
-use once_cell::sync::Lazy;
-use metal_archives::MetalArchives;
+use crate::body::download::provider::append_providers_button;
 use crate::common::check_button_dialog::check_button_dialog;
 use crate::common::constant::SUGGESTED_ACTION;
 
 pub mod albums;
+mod local;
+mod provider;
 pub mod songs;
 
-static METAL_ARCHIVES: Lazy<MetalArchives> = Lazy::new(|| { MetalArchives::new() });
-
 fn append_download_button<DR: 'static, D: Fn(Sender<DR>) + 'static, S,
     HD: Fn(DR, Box<dyn Fn(anyhow::Result<Vec<anyhow::Result<S>>>)>,
         Box<dyn Fn(usize, anyhow::Result<Bytes>, Box<dyn Fn(&gtk::Box, &Image)>, usize)>) + Clone + 'static,
@@ -32,6 +31,7 @@ fn append_download_button<DR: 'static, D: Fn(Sender<DR>) + 'static, S,
     handle_search: HS, choose_option: CO, menu_button: MenuButton) {
     let download_button = Button::builder().label(format!("Download {download_label}")).build();
     gtk_box.append(&download_button);
+    append_providers_button(gtk_box, menu_button.clone());
     download_button.connect_clicked(move |_| {
         let (sender, receiver) = channel::<DR>();
         download(sender);
@@ -139,7 +139,7 @@ fn save(path: impl AsRef<Path>, vec: Bytes) {
     }
 }
 
-fn handle_scroll(scroll_adjustment: Option<f64>, adjustment: Adjustment) {
+pub(in crate::body) fn handle_scroll(scroll_adjustment: Option<f64>, adjustment: Adjustment) {
     if let Some(scroll_adjustment) = scroll_adjustment {
         timeout_add_local_once(Duration::from_millis(150), move || { adjustment.set_value(scroll_adjustment); });
     }
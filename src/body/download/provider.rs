@@ -0,0 +1,124 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use adw::prelude::*;
+use adw::Window;
+use diesel::{ExpressionMethods, insert_into, QueryDsl, RunQueryDsl, update};
+use diesel::dsl::max;
+use gtk::{Button, CheckButton, Label, MenuButton, ScrolledWindow, Separator};
+use gtk::Align::Center;
+use gtk::Orientation::{Horizontal, Vertical};
+use once_cell::sync::Lazy;
+use metadata_fetch::{MetadataFetcher, Registry};
+use metal_archives::MetalArchives;
+use crate::body::download::local::LocalFolder;
+use crate::common::{gtk_box, StyledWidget};
+use crate::db::get_connection;
+use crate::schema::providers::{enabled, name, position};
+use crate::schema::providers::dsl::providers;
+
+const METADATA_PROVIDERS: &'static str = "Metadata providers";
+
+static METAL_ARCHIVES: Lazy<MetalArchives> = Lazy::new(|| { MetalArchives::new() });
+static LOCAL_FOLDER: LocalFolder = LocalFolder;
+
+fn available() -> Vec<&'static dyn MetadataFetcher> {
+    vec![&*METAL_ARCHIVES, &LOCAL_FOLDER]
+}
+
+fn ordered() -> Vec<(&'static dyn MetadataFetcher, bool)> {
+    let mut connection = get_connection();
+    let rows = providers.order_by(position).select((name, enabled)).get_results::<(String, i32)>(&mut connection)
+        .unwrap();
+    let mut available = available();
+    let mut ordered = rows.into_iter().filter_map(|(provider_name, enabled_int)| {
+        available.iter().position(|provider| { provider.name() == provider_name })
+            .map(|i| { (available.remove(i), enabled_int == 1) })
+    }).collect::<Vec<_>>();
+    for provider in available {
+        let last = providers.select(max(position)).get_result::<Option<i32>>(&mut connection).unwrap();
+        insert_into(providers).values((name.eq(provider.name()), position.eq(last.map(|it| { it + 1 }).unwrap_or(0))))
+            .execute(&mut connection).unwrap();
+        ordered.push((provider, true));
+    }
+    ordered
+}
+
+pub(in crate::body::download) fn registry() -> Registry {
+    Registry::new(ordered().into_iter().filter(|(_, enabled_bool)| { *enabled_bool })
+        .map(|(provider, _)| { provider }).collect())
+}
+
+fn save(ordered: &Vec<(&'static dyn MetadataFetcher, bool)>) {
+    let mut connection = get_connection();
+    for (i, (provider, enabled_bool)) in ordered.iter().enumerate() {
+        update(providers.find(provider.name())).set((position.eq(i as i32), enabled.eq(*enabled_bool as i32)))
+            .execute(&mut connection).unwrap();
+    }
+}
+
+fn render(providers_box: &gtk::Box, ordered: Rc<RefCell<Vec<(&'static dyn MetadataFetcher, bool)>>>) {
+    while let Some(child) = providers_box.first_child() { providers_box.remove(&child); }
+    providers_box.append(&Separator::builder().hexpand(true).build());
+    let count = ordered.borrow().len();
+    for (i, (provider, enabled_bool)) in ordered.borrow().iter().enumerate() {
+        let provider_row = gtk_box(Horizontal);
+        providers_box.append(&provider_row);
+        providers_box.append(&Separator::builder().hexpand(true).build());
+        let check_button = CheckButton::builder().child(&Label::new(Some(provider.name()))).active(*enabled_bool)
+            .hexpand(true).build();
+        provider_row.append(&check_button);
+        check_button.connect_toggled({
+            let ordered = ordered.clone();
+            move |check_button| {
+                ordered.borrow_mut()[i].1 = check_button.is_active();
+                save(&ordered.borrow());
+            }
+        });
+        for (icon_name, tooltip, other) in [("go-up-symbolic", "Query earlier", i.checked_sub(1)),
+            ("go-down-symbolic", "Query later", Some(i + 1).filter(|it| { *it < count }))] {
+            let move_button = Button::builder().icon_name(icon_name).tooltip_text(tooltip).has_frame(false)
+                .sensitive(other.is_some()).build();
+            provider_row.append(&move_button);
+            move_button.connect_clicked({
+                let providers_box = providers_box.clone();
+                let ordered = ordered.clone();
+                move |_| {
+                    ordered.borrow_mut().swap(i, other.unwrap());
+                    save(&ordered.borrow());
+                    render(&providers_box, ordered.clone());
+                }
+            });
+        }
+    }
+}
+
+fn providers_dialog() {
+    let main_box = gtk::Box::builder().orientation(Vertical).spacing(4)
+        .margin_start(12).margin_end(12).margin_top(12).margin_bottom(12).build();
+    let scrolled_window = ScrolledWindow::builder().child(&main_box)
+        .propagate_natural_width(true).propagate_natural_height(true).build();
+    let dialog = Window::builder().title(METADATA_PROVIDERS).modal(true).content(&scrolled_window).build();
+    main_box.append(&Label::new(Some(METADATA_PROVIDERS)).with_css_class("heading"));
+    main_box.append(&Label::new(Some("Search results are listed in this order")));
+    let providers_box = gtk::Box::builder().orientation(Vertical).margin_top(16).margin_bottom(16).build();
+    main_box.append(&providers_box);
+    render(&providers_box, Rc::new(RefCell::new(ordered())));
+    let button_box = gtk::Box::builder().spacing(16).halign(Center).build();
+    main_box.append(&button_box);
+    let close_button = Button::builder().label("Close").build();
+    button_box.append(&close_button);
+    close_button.connect_clicked({
+        let dialog = dialog.clone();
+        move |_| { dialog.close(); }
+    });
+    dialog.present();
+}
+
+pub(in crate::body::download) fn append_providers_button(gtk_box: &gtk::Box, menu_button: MenuButton) {
+    let providers_button = Button::builder().label(METADATA_PROVIDERS).build();
+    gtk_box.append(&providers_button);
+    providers_button.connect_clicked(move |_| {
+        providers_dialog();
+        menu_button.popdown();
+    });
+}
@@ -1,6 +1,7 @@
 use std::cell::Cell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::hard_link;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
 use adw::NavigationPage;
@@ -10,28 +11,42 @@ use adw::gdk::pango::Weight::Bold;
 use adw::gio::{Cancellable, ListStore};
 use adw::prelude::*;
 use diesel::RunQueryDsl;
-use gtk::{Button, FileDialog, FileFilter, GestureClick, Grid, Label, Separator};
+use gtk::{Button, FileDialog, FileFilter, GestureClick, Grid, Image, Label, MenuButton, Popover, Separator};
 use gtk::Orientation::Vertical;
 use log::{error, warn};
-use metadata_fetch::{AlbumSearch, MetadataFetcher};
+use metadata_fetch::AlbumSearch;
 use metadata_fetch::DownloadAlbumEvent::{Cover, SearchResult};
-use crate::body::{action_name, Body, BodyType, handle_render, POP_DOWN, SONG};
-use crate::body::download::{append_download_button, handle_scroll, METAL_ARCHIVES, save};
-use crate::common::constant::INSENSITIVE_FG;
+use crate::body::{action_name, append_queue_buttons, Body, BodyType, handle_render, POP_DOWN, SONG};
+use crate::body::download::{append_download_button, handle_scroll, save};
+use crate::body::download::provider::registry;
+use crate::body::tag_editor;
+use crate::common::{FOLDER_MUSIC_ICON, ImagePathBuf, StyledLabelBuilder};
+use crate::common::constant::{INSENSITIVE_FG, NONE};
 use crate::common::state::State;
-use crate::common::StyledLabelBuilder;
 use crate::common::util::{format, or_none_arc, Plural};
 use crate::config::Config;
 use crate::db::get_connection;
+use crate::queue::follow_album;
 use crate::schema::config::dsl::config;
-use crate::song::{get_current_album, join_path};
+use crate::song::{get_current_album, WithImage, WithPath};
+
+fn disc_header(album_volume: Option<i32>, song_path: PathBuf) -> gtk::Box {
+    let disc_header = gtk::Box::builder().spacing(8).margin_start(8).margin_top(16).margin_bottom(4).build();
+    if let Some(disc_cover) = song_path.disc_cover() {
+        disc_header.append(Image::builder().pixel_size(32).build().set_or_default(&disc_cover, FOLDER_MUSIC_ICON));
+    }
+    disc_header.append(&Label::builder().label(&album_volume.map(|it| { format!("Disc {it}") })
+        .unwrap_or(String::from(NONE))).bold().build());
+    disc_header
+}
 
 pub fn songs_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_adjustment: Option<f64>)
     -> NavigationPage {
-    let (album_string, artist_string, cover) = {
+    let (album_id, album_string, artist_string, cover) = {
         let mut params = params.clone();
-        (params.pop().unwrap(), params.pop().unwrap(), params.pop().unwrap())
+        (params.pop().unwrap(), params.pop().unwrap(), params.pop().unwrap(), params.pop().unwrap())
     };
+    let album_id = album_id.and_then(|it| { it.parse::<i32>().ok() });
     let body = Body::new(&*or_none_arc(album_string.clone()), state.clone(), None, params, BodyType::Songs);
     let cover = cover.clone().unwrap();
     let select_cover = Button::builder().label("Choose cover").tooltip_text("Choose album cover from local files")
@@ -62,12 +77,18 @@ pub fn songs_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_adj
             select_cover.activate_action(&action_name(POP_DOWN), None).unwrap();
         }
     });
+    let album_song_ids = move || {
+        get_current_album(album_id, &mut get_connection()).into_iter().map(|(song, _)| { song.id }).collect::<Vec<_>>()
+    };
+    append_queue_buttons(Some("album"), &body.popover_box, &body.menu_button, album_song_ids);
+    body.popover_box.append(&tag_editor::button("Edit album tags", state.clone(), &body.menu_button,
+        album_song_ids));
     if let Some(artist_string) = artist_string.clone() {
         if let Some(album_string) = album_string.clone() {
             append_download_button("cover", &body.popover_box, {
                 let artist_string = artist_string.clone();
                 let album_string = album_string.clone();
-                move |sender| { METAL_ARCHIVES.download_cover(&artist_string, &album_string, sender); }
+                move |sender| { registry().download_cover(&artist_string, &album_string, sender); }
             }, 1, move |search_result, handle_search_result, handle_bytes| {
                 match search_result {
                     SearchResult(search_result) => { handle_search_result(search_result); }
@@ -75,13 +96,14 @@ pub fn songs_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_adj
                         handle_bytes(i, cover, Box::new(|gtk_box, image| { gtk_box.prepend(image); }), 0);
                     }
                 }
-            }, |AlbumSearch { artist, album, album_type }| {
+            }, |AlbumSearch { provider, artist, album, album_type }| {
                 let gtk_box = gtk::Box::builder().orientation(Vertical).spacing(4).hexpand(true).margin_start(4)
                     .build();
                 gtk_box.append(&Label::builder().label(&album).bold().wrap(true).build());
                 gtk_box.append(&Label::builder().label(&album_type).wrap(true).build());
                 gtk_box.append(&Label::builder().label(&artist).wrap(true).subscript().name(INSENSITIVE_FG)
                     .build());
+                gtk_box.append(&Label::builder().label(provider).subscript().name(INSENSITIVE_FG).build());
                 gtk_box
             }, move |images_vec, i| {
                 save(&*cover, images_vec[0].borrow_mut().remove(i).unwrap());
@@ -90,13 +112,23 @@ pub fn songs_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_adj
     }
     let adjustment = body.scrolled_window.vadjustment();
     let render = move || {
-        let current_album = get_current_album(&artist_string, &album_string, &mut get_connection());
+        let current_album = get_current_album(album_id, &mut get_connection());
         body.window_title.set_subtitle(&current_album.len().number_plural(SONG));
         let Config { current_song_id, .. } = config.get_result::<Config>(&mut get_connection()).unwrap();
         let current_song_id = Cell::new(current_song_id);
         let grid = Grid::new();
-        let song_id_to_labels = current_album.iter().enumerate().map(|(row, (song, collection))| {
-            let grid_row = (2 * row) as i32;
+        let multi_disc = current_album.iter().map(|(song, _)| { song.album_volume }).collect::<HashSet<_>>().len() > 1;
+        let mut current_volume = None;
+        let mut next_row = 0;
+        let song_id_to_labels = current_album.iter().map(|(song, collection)| {
+            if multi_disc && current_volume != Some(song.album_volume) {
+                current_volume = Some(song.album_volume);
+                grid.attach(&disc_header(song.album_volume, (song, collection).path()), 0, next_row, 4, 1);
+                grid.attach(&Separator::builder().build(), 0, next_row + 1, 4, 1);
+                next_row += 2;
+            }
+            let grid_row = next_row;
+            next_row += 2;
             let separator_row = grid_row + 1;
             let track_number_builder = Label::builder().margin_start(8).margin_end(8);
             let track_number_label = if let Some(track_number) = song.track_number {
@@ -114,14 +146,30 @@ pub fn songs_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_adj
                 .margin_start(8).margin_end(8).build();
             grid.attach(&duration_label, 2, grid_row, 1, 1);
             grid.attach(&Separator::builder().build(), 2, separator_row, 1, 1);
+            let song_popover_box = gtk::Box::builder().orientation(Vertical).build();
+            let song_menu_button = MenuButton::builder().icon_name("view-more-symbolic").has_frame(false)
+                .margin_end(4).popover(&Popover::builder().child(&song_popover_box).build()).build();
+            append_queue_buttons(None, &song_popover_box, &song_menu_button, {
+                let song_id = song.id;
+                move || { vec![song_id] }
+            });
+            song_popover_box.append(&tag_editor::button("Edit tags", state.clone(), &song_menu_button, {
+                let song_id = song.id;
+                move || { vec![song_id] }
+            }));
+            grid.attach(&song_menu_button, 3, grid_row, 1, 1);
+            grid.attach(&Separator::builder().build(), 3, separator_row, 1, 1);
             let labels = vec![track_number_label, title_label, duration_label];
-            let path = Rc::new(join_path(&collection.path, &song.path));
+            let path = Rc::new((song, collection).path());
             for label in &labels {
                 let gesture_click = GestureClick::new();
                 gesture_click.connect_released({
                     let state = state.clone();
                     let path = path.clone();
-                    move |_, _, _, _| { state.window_actions.song_selected.activate(path.to_str().unwrap()); }
+                    move |_, _, _, _| {
+                        follow_album();
+                        state.window_actions.song_selected.activate(path.to_str().unwrap());
+                    }
                 });
                 label.add_controller(gesture_click);
             }
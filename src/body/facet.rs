@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, HashSet};
+use std::rc::Rc;
+use std::sync::Arc;
+use adw::NavigationPage;
+use adw::prelude::*;
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::dsl::count_star;
+use diesel::expression_methods::SqliteExpressionMethods;
+use gtk::{Button, GestureClick, Label, MenuButton, Separator};
+use gtk::Orientation::Vertical;
+use crate::body::{ALBUM, Body, BodyType, handle_render, next_icon, SONG};
+use crate::body::download::albums::albums_page;
+use crate::body::download::handle_scroll;
+use crate::body::merge::Query;
+use crate::common::StyledLabelBuilder;
+use crate::common::constant::{INSENSITIVE_FG, NONE};
+use crate::common::state::State;
+use crate::common::util::{or_none, Plural};
+use crate::db::get_connection;
+use crate::schema::collections::dsl::collections;
+use crate::schema::songs::{album, album_id, album_volume, artist, genre, id, track_number, year};
+use crate::schema::songs::dsl::songs;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Facet {
+    Genre,
+    Year,
+}
+
+impl Facet {
+    pub fn entity(self) -> &'static str {
+        match self {
+            Facet::Genre => { "Genre" }
+            Facet::Year => { "Year" }
+        }
+    }
+    pub fn param(self) -> &'static str {
+        match self {
+            Facet::Genre => { "genre" }
+            Facet::Year => { "year" }
+        }
+    }
+    pub fn from_param(param: &str) -> Option<Self> {
+        [Facet::Genre, Facet::Year].into_iter().find(|facet| { facet.param() == param })
+    }
+    fn body_type(self) -> BodyType {
+        match self {
+            Facet::Genre => { BodyType::Genres }
+            Facet::Year => { BodyType::Years }
+        }
+    }
+    pub(in crate::body) fn filter(self, value: &Option<Arc<String>>) -> Query {
+        match self {
+            Facet::Genre => {
+                let key = value.as_ref().map(|it| { it.to_lowercase() });
+                let genres = songs.select(genre).distinct().get_results::<Option<String>>(&mut get_connection())
+                    .unwrap().into_iter().filter(|genre_string| {
+                    genre_parts(genre_string).iter().any(|part| { genre_key(part) == key })
+                }).collect::<Vec<_>>();
+                if genres.contains(&None) {
+                    Box::new(genre.is_null().or(genre.eq_any(genres.into_iter().flatten().collect::<Vec<_>>())))
+                } else {
+                    Box::new(genre.eq_any(genres.into_iter().flatten().collect::<Vec<_>>()))
+                }
+            }
+            Facet::Year => { Box::new(year.is(value.as_ref().and_then(|it| { it.parse::<i32>().ok() }))) }
+        }
+    }
+    pub(in crate::body) fn song_ids(self, value: &Option<Arc<String>>) -> Vec<i32> {
+        songs.inner_join(collections).filter(self.filter(value))
+            .order_by((year, artist, album, album_volume, track_number, id)).select(id)
+            .get_results::<i32>(&mut get_connection()).unwrap()
+    }
+}
+
+fn genre_parts(genre_string: &Option<String>) -> Vec<Option<String>> {
+    let parts = genre_string.iter().flat_map(|it| { it.split(|c| { matches!(c, ';' | ',') }) })
+        .map(str::trim).filter(|it| { !it.is_empty() }).map(|it| { Some(String::from(it)) }).collect::<Vec<_>>();
+    if parts.is_empty() { vec![None] } else { parts }
+}
+
+fn genre_key(part: &Option<String>) -> Option<String> {
+    part.as_ref().map(|it| { it.to_lowercase() })
+}
+
+fn facet_values(facet: Facet) -> Vec<(Option<String>, usize, i64)> {
+    let mut values = BTreeMap::<Option<String>, (Option<String>, HashSet<Option<i32>>, i64)>::new();
+    let mut add = |key: Option<String>, label: Option<String>, album_id_option: Option<i32>, count: i64| {
+        let (_, albums, song_count) = values.entry(key).or_insert_with(|| { (label, HashSet::new(), 0) });
+        albums.insert(album_id_option);
+        *song_count += count;
+    };
+    match facet {
+        Facet::Genre => {
+            for (genre_string, album_id_option, count) in songs.group_by((genre, album_id))
+                .select((genre, album_id, count_star()))
+                .get_results::<(Option<String>, Option<i32>, i64)>(&mut get_connection()).unwrap() {
+                for part in genre_parts(&genre_string) { add(genre_key(&part), part, album_id_option, count); }
+            }
+        }
+        Facet::Year => {
+            for (year_int, album_id_option, count) in songs.group_by((year, album_id))
+                .select((year, album_id, count_star()))
+                .get_results::<(Option<i32>, Option<i32>, i64)>(&mut get_connection()).unwrap() {
+                add(year_int.map(|it| { format!("{it:04}") }), year_int.map(|it| { it.to_string() }), album_id_option,
+                    count);
+            }
+        }
+    }
+    let values = values.into_values().map(|(label, albums, song_count)| { (label, albums.len(), song_count) });
+    if facet == Facet::Year { values.rev().collect() } else { values.collect() }
+}
+
+fn decade(year_string: &Option<String>) -> Option<i32> {
+    year_string.as_ref().and_then(|it| { it.parse::<i32>().ok() }).map(|it| { it.div_euclid(10) * 10 })
+}
+
+pub fn facet_page(facet: Facet, state: Rc<State>, scroll_adjustment: Option<f64>) -> NavigationPage {
+    let title = format!("{}s", facet.entity());
+    let body = Body::new(&title, state.clone(), None, Vec::new(), facet.body_type());
+    let adjustment = body.scrolled_window.vadjustment();
+    let render = move || {
+        let facets_box = gtk::Box::builder().orientation(Vertical).build();
+        let values = facet_values(facet);
+        body.window_title.set_subtitle(&values.len().number_plural(facet.entity()));
+        let mut current_decade = None;
+        for (value, album_count, song_count) in values {
+            if facet == Facet::Year && current_decade != Some(decade(&value)) {
+                current_decade = Some(decade(&value));
+                facets_box.append(&Label::builder().label(&current_decade.flatten().map(|it| { format!("{it}s") })
+                    .unwrap_or(String::from(NONE))).bold().xalign(0.0).margin_start(8).margin_top(16)
+                    .margin_bottom(4).build());
+                facets_box.append(&Separator::builder().build());
+            }
+            let facet_row = gtk::Box::builder().spacing(8).build();
+            facets_box.append(&facet_row);
+            facets_box.append(&Separator::builder().build());
+            let facet_box = gtk::Box::builder().orientation(Vertical).hexpand(true)
+                .margin_start(8).margin_end(4).margin_top(12).margin_bottom(12).build();
+            facet_row.append(&facet_box);
+            facet_box.append(&Label::builder().label(or_none(&value)).ellipsized().build());
+            let count_box = gtk::Box::builder().spacing(4).name(INSENSITIVE_FG).build();
+            facet_box.append(&count_box);
+            let album_count_box = gtk::Box::builder().spacing(4).build();
+            count_box.append(&album_count_box);
+            album_count_box.append(&Label::builder().label(&album_count.to_string()).subscript().build());
+            album_count_box.append(&Label::builder().label(album_count.plural(ALBUM)).subscript().build());
+            let song_count_box = gtk::Box::builder().spacing(4).build();
+            count_box.append(&song_count_box);
+            song_count_box.append(&Label::builder().label(&song_count.to_string()).subscript().build());
+            song_count_box.append(&Label::builder().label(song_count.plural(SONG)).subscript().build());
+            facet_row.append(&next_icon());
+            let gesture_click = GestureClick::new();
+            gesture_click.connect_released({
+                let state = state.clone();
+                let value = value.map(Arc::new);
+                move |_, _, _, _| {
+                    state.navigation_view.push(&albums_page(vec![None, None,
+                        Some(Arc::new(String::from(facet.param()))), value.clone()], state.clone(), None));
+                }
+            });
+            facet_row.add_controller(gesture_click);
+        }
+        body.scrolled_window.set_child(Some(&facets_box));
+    };
+    handle_scroll(scroll_adjustment, adjustment);
+    handle_render(render, body.rerender);
+    body.navigation_page
+}
+
+pub(in crate::body) fn button(facet: Facet, state: Rc<State>, menu_button: &MenuButton) -> Button {
+    let facet_button = Button::builder().label(&format!("{}s", facet.entity())).build();
+    let menu_button = menu_button.clone();
+    facet_button.connect_clicked(move |_| {
+        state.navigation_view.push(&facet_page(facet, state.clone(), None));
+        menu_button.popdown();
+    });
+    facet_button
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::body::facet::{genre_key, genre_parts};
+
+    fn parts(genre_string: &str) -> Vec<Option<String>> {
+        genre_parts(&Some(String::from(genre_string)))
+    }
+
+    #[test]
+    fn genre_parts_split_on_semicolons_and_commas() {
+        assert_eq!(parts("Rock; Pop,Jazz"),
+            vec![Some(String::from("Rock")), Some(String::from("Pop")), Some(String::from("Jazz"))]);
+    }
+
+    #[test]
+    fn genre_parts_keep_slashes() {
+        assert_eq!(parts("AC/DC Tribute; Drum & Bass/Jungle"),
+            vec![Some(String::from("AC/DC Tribute")), Some(String::from("Drum & Bass/Jungle"))]);
+    }
+
+    #[test]
+    fn genre_parts_without_a_genre_are_none() {
+        assert_eq!(genre_parts(&None), vec![None]);
+        assert_eq!(parts(" ; , "), vec![None]);
+    }
+
+    #[test]
+    fn genre_keys_ignore_case() {
+        assert_eq!(genre_key(&Some(String::from("Hip-Hop"))), genre_key(&Some(String::from("hip-hop"))));
+    }
+}
@@ -0,0 +1,117 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::mem::take;
+use strsim::levenshtein;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+const ARTICLE: &'static str = "the ";
+const REMASTERED: [&'static str; 2] = ["remaster", "remastered"];
+
+fn without_remastered(name: &str) -> &str {
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        if let Some(start) = name.rfind(open) {
+            let suffix = name[start + 1..].trim_end().trim_end_matches(close);
+            if name.trim_end().ends_with(close)
+                && suffix.split_whitespace().any(|word| { REMASTERED.contains(&word) }) {
+                return name[..start].trim_end();
+            }
+        }
+    }
+    if let Some(start) = name.rfind(" - ") {
+        if name[start + 3..].split_whitespace().any(|word| { REMASTERED.contains(&word) }) {
+            return name[..start].trim_end();
+        }
+    }
+    name
+}
+
+fn normalize(name: &str, album: bool) -> String {
+    let folded = name.nfd().filter(|c| { !is_combining_mark(*c) }).collect::<String>().to_lowercase()
+        .split_whitespace().collect::<Vec<_>>().join(" ");
+    let folded = if album { String::from(without_remastered(&folded)) } else { folded };
+    folded.strip_prefix(ARTICLE).map(String::from).unwrap_or(folded)
+}
+
+fn typo_distance(key: &str) -> usize {
+    match key.chars().count() {
+        0..=4 => { 0 }
+        5..=10 => { 1 }
+        _ => { 2 }
+    }
+}
+
+pub(in crate::body) fn suggestions(names: Vec<String>, album: bool) -> Vec<Vec<String>> {
+    let mut by_key = BTreeMap::<String, Vec<String>>::new();
+    for name in names { by_key.entry(normalize(&name, album)).or_default().push(name); }
+    // typos are only looked for among keys sharing their first letter
+    let mut buckets = BTreeMap::<Option<char>, Vec<(String, Vec<String>)>>::new();
+    for (key, names) in by_key { buckets.entry(key.chars().next()).or_default().push((key, names)); }
+    let mut groups = Vec::new();
+    for mut bucket in buckets.into_values() {
+        // the most common spelling leads its group and only keys close to it join, so that typos don't chain
+        bucket.sort_by_key(|(_, names)| { Reverse(names.len()) });
+        let (keys, mut names) = bucket.into_iter().unzip::<_, _, Vec<_>, Vec<_>>();
+        for i in 0..keys.len() {
+            if names[i].is_empty() { continue; }
+            let mut group = take(&mut names[i]);
+            let length = keys[i].chars().count();
+            for j in i + 1..keys.len() {
+                let distance = typo_distance(&keys[i]).min(typo_distance(&keys[j]));
+                if !names[j].is_empty() && distance > 0 && length.abs_diff(keys[j].chars().count()) <= distance
+                    && levenshtein(&keys[i], &keys[j]) <= distance {
+                    group.extend(take(&mut names[j]));
+                }
+            }
+            if group.len() > 1 { groups.push(group); }
+        }
+    }
+    groups.sort_by_key(|group| { Reverse(group.len()) });
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::body::merge::duplicate::{normalize, suggestions};
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|it| { String::from(*it) }).collect()
+    }
+
+    #[test]
+    fn normalize_folds_case_whitespace_diacritics_and_the_article() {
+        assert_eq!(normalize("The  Beatles", false), "beatles");
+        assert_eq!(normalize("Motörhead", false), "motorhead");
+        assert_eq!(normalize("Theatre of Tragedy", false), "theatre of tragedy");
+    }
+
+    #[test]
+    fn normalize_drops_a_remastered_suffix_of_albums_only() {
+        assert_eq!(normalize("Abbey Road (2009 Remaster)", true), "abbey road");
+        assert_eq!(normalize("Abbey Road - Remastered", true), "abbey road");
+        assert_eq!(normalize("Abbey Road (2009 Remaster)", false), "abbey road (2009 remaster)");
+    }
+
+    #[test]
+    fn suggestions_group_spellings_of_the_same_name() {
+        assert_eq!(suggestions(names(&["The Beatles", "Beatles", "Metallica", "Metalica"]), false),
+            vec![names(&["The Beatles", "Beatles"]), names(&["Metalica", "Metallica"])]);
+    }
+
+    #[test]
+    fn suggestions_only_compare_names_with_the_same_first_letter() {
+        assert!(suggestions(names(&["Slayer", "Player"]), false).is_empty());
+    }
+
+    #[test]
+    fn suggestions_leave_short_names_to_exact_matches() {
+        assert!(suggestions(names(&["Abba", "Abbe"]), false).is_empty());
+        assert_eq!(suggestions(names(&["ABBA", "Abba"]), false), vec![names(&["ABBA", "Abba"])]);
+    }
+
+    #[test]
+    fn suggestions_do_not_chain_typos() {
+        assert_eq!(suggestions(names(&["Metallica", "Metallica", "Metalica", "Metalic"]), false),
+            vec![names(&["Metallica", "Metallica", "Metalica"])]);
+    }
+}
@@ -0,0 +1,193 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, TryRecvError::*};
+use std::thread;
+use std::time::Duration;
+use adw::{NavigationPage, Window};
+use adw::glib::{ControlFlow::*, timeout_add_local};
+use adw::prelude::*;
+use gtk::{Button, Label, MenuButton, Overlay, ProgressBar, ScrolledWindow, Separator};
+use gtk::Align::Center;
+use gtk::Orientation::{Horizontal, Vertical};
+use log::error;
+use crate::body::{action_name, Body, BodyType, handle_render, RERENDER, SONG};
+use crate::body::download::handle_scroll;
+use crate::body::merge::journal::{history, Merge, originals, undo};
+use crate::common::{gtk_box, StyledLabelBuilder, StyledWidget};
+use crate::common::constant::{DESTRUCTIVE_ACTION, INSENSITIVE_FG};
+use crate::common::state::State;
+use crate::common::util::{or_none, Plural};
+use crate::tag::WriteProgress;
+
+const MERGE_HISTORY: &'static str = "Merge history";
+
+fn describe(merge: &Merge) -> (String, String) {
+    let mut merged = originals(merge);
+    let song_count = merged.len();
+    merged.sort();
+    merged.dedup();
+    (format!("{} → {}", merged.iter().map(or_none).collect::<Vec<_>>().join(", "), merge.value),
+        format!("{} of {}", merge.field.label(), song_count.number_plural(SONG)))
+}
+
+fn undo_dialog<F: Fn() + 'static>(merge: Merge, on_undone: F) {
+    let main_box = gtk::Box::builder().orientation(Vertical).spacing(4)
+        .margin_start(12).margin_end(12).margin_top(12).margin_bottom(12).build();
+    let scrolled_window = ScrolledWindow::builder().child(&main_box)
+        .propagate_natural_width(true).propagate_natural_height(true).build();
+    let overlay = Overlay::builder().child(&scrolled_window).build();
+    let dialog = Window::builder().title("Undo merge").modal(true).content(&overlay).build();
+    main_box.append(&Label::new(Some("Undo merge")).with_css_class("heading"));
+    let (merged, subtitle) = describe(&merge);
+    main_box.append(&Label::builder().label(&merged).wrap(true).margin_top(16).build());
+    main_box.append(&Label::builder().label(&subtitle).subscript().name(INSENSITIVE_FG).margin_bottom(16).build());
+    let error_label = Label::builder().wrap(true).visible(false).build().with_css_class("error");
+    main_box.append(&error_label);
+    let button_box = gtk::Box::builder().spacing(16).halign(Center).build();
+    main_box.append(&button_box);
+    let cancel_button = Button::builder().label("Cancel").build();
+    button_box.append(&cancel_button);
+    cancel_button.connect_clicked({
+        let dialog = dialog.clone();
+        move |_| { dialog.close(); }
+    });
+    let undo_button = Button::builder().label("Restore original tags").build().with_css_class(DESTRUCTIVE_ACTION);
+    button_box.append(&undo_button);
+    let on_undone = Rc::new(on_undone);
+    undo_button.connect_clicked({
+        let dialog = dialog.clone();
+        move |undo_button| {
+            undo_button.set_sensitive(false);
+            cancel_button.set_sensitive(false);
+            let progress_bar = ProgressBar::builder().hexpand(true).build().osd();
+            overlay.add_overlay(&progress_bar);
+            let (sender, receiver) = channel::<WriteProgress>();
+            let (kept_sender, kept_receiver) = channel::<Vec<String>>();
+            thread::spawn({
+                let merge = merge.clone();
+                move || {
+                    match undo(&merge, &sender) {
+                        Ok(kept) => { kept_sender.send(kept).unwrap(); }
+                        Err(error) => {
+                            error!("error undoing merge [{merge:?}] [{error}]");
+                            sender.send(WriteProgress::Failed(error.to_string())).unwrap();
+                        }
+                    }
+                }
+            });
+            let failed = Cell::new(false);
+            timeout_add_local(Duration::from_millis(500), {
+                let overlay = overlay.clone();
+                let dialog = dialog.clone();
+                let cancel_button = cancel_button.clone();
+                let error_label = error_label.clone();
+                let on_undone = on_undone.clone();
+                move || {
+                    loop {
+                        match receiver.try_recv() {
+                            Err(Empty) => { break; }
+                            Err(Disconnected) => {
+                                overlay.remove_overlay(&progress_bar);
+                                if failed.get() {
+                                    cancel_button.set_sensitive(true);
+                                    return Break;
+                                }
+                                on_undone();
+                                match kept_receiver.try_recv() {
+                                    Ok(kept) if !kept.is_empty() => {
+                                        error_label.set_label(&format!(
+                                            "{} changed since the merge kept their tags:\n{}",
+                                            kept.len().number_plural(SONG), kept.join("\n")));
+                                        error_label.set_visible(true);
+                                        cancel_button.set_label("Close");
+                                        cancel_button.set_sensitive(true);
+                                    }
+                                    _ => { dialog.close(); }
+                                }
+                                return Break;
+                            }
+                            Ok(WriteProgress::Fraction(fraction)) => { progress_bar.set_fraction(fraction); }
+                            Ok(WriteProgress::Failed(message)) => {
+                                failed.set(true);
+                                error_label.set_label(&format!("Nothing was restored: {message}"));
+                                error_label.set_visible(true);
+                            }
+                        }
+                    }
+                    Continue
+                }
+            });
+        }
+    });
+    dialog.present();
+}
+
+pub fn merge_history_page(state: Rc<State>, scroll_adjustment: Option<f64>) -> NavigationPage {
+    let body = Body::new(MERGE_HISTORY, state, None, Vec::new(), BodyType::MergeHistory);
+    let adjustment = body.scrolled_window.vadjustment();
+    let render = {
+        let rerender = body.rerender.clone();
+        move || {
+            let merges_box = gtk::Box::builder().orientation(Vertical).build();
+            let merges = history();
+            body.window_title.set_subtitle(&merges.len().number_plural("Merge"));
+            for merge in merges {
+                let merge_row = gtk_box(Horizontal);
+                merges_box.append(&merge_row);
+                merges_box.append(&Separator::builder().build());
+                let label_box = gtk::Box::builder().orientation(Vertical).hexpand(true)
+                    .margin_start(8).margin_end(4).margin_top(12).margin_bottom(12).build();
+                merge_row.append(&label_box);
+                let (merged, subtitle) = describe(&merge);
+                label_box.append(&Label::builder().label(&merged).xalign(0.0).ellipsized().build());
+                label_box.append(&Label::builder().label(&subtitle).xalign(0.0).subscript().name(INSENSITIVE_FG)
+                    .build());
+                let undo_button = Button::builder().icon_name("edit-undo-symbolic").tooltip_text("Undo")
+                    .has_frame(false).margin_end(4).build();
+                merge_row.append(&undo_button);
+                undo_button.connect_clicked({
+                    let rerender = rerender.clone();
+                    move |_| {
+                        let rerender = rerender.clone();
+                        undo_dialog(merge.clone(), move || { rerender.activate(None); });
+                    }
+                });
+            }
+            body.scrolled_window.set_child(Some(&merges_box));
+        }
+    };
+    handle_scroll(scroll_adjustment, adjustment);
+    handle_render(render, body.rerender);
+    body.navigation_page
+}
+
+pub(in crate::body) fn append_buttons(state: Rc<State>, menu_button: &MenuButton, popover_box: &gtk::Box) {
+    let undo_button = Button::builder().label("Undo last merge").build();
+    popover_box.append(&undo_button);
+    undo_button.connect_clicked({
+        let state = state.clone();
+        let menu_button = menu_button.clone();
+        move |_| {
+            if let Some(merge) = history().into_iter().next() {
+                let state = state.clone();
+                undo_dialog(merge, move || {
+                    if let Some(navigation_page) = state.navigation_view.visible_page() {
+                        navigation_page.activate_action(&action_name(RERENDER), None).ok();
+                    }
+                });
+            }
+            menu_button.popdown();
+        }
+    });
+    menu_button.popover().unwrap().connect_show({
+        let undo_button = undo_button.clone();
+        move |_| { undo_button.set_sensitive(!history().is_empty()); }
+    });
+    let history_button = Button::builder().label(MERGE_HISTORY).build();
+    popover_box.append(&history_button);
+    let menu_button = menu_button.clone();
+    history_button.connect_clicked(move |_| {
+        state.navigation_view.push(&merge_history_page(state.clone(), None));
+        menu_button.popdown();
+    });
+}
@@ -1,5 +1,5 @@
 use std::cell::{Cell, RefCell};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::mpsc::{channel, TryRecvError::*};
@@ -9,37 +9,276 @@ use adw::{HeaderBar, Window};
 use adw::gio::{SimpleAction, SimpleActionGroup};
 use adw::glib::{ControlFlow::*, timeout_add_local, Variant};
 use adw::prelude::*;
-use diesel::{BoolExpressionMethods, QueryDsl, RunQueryDsl};
-use gtk::{Button, CheckButton, GestureClick, GestureZoom, Label, MenuButton, Overlay, ProgressBar};
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
+use gtk::{Button, CheckButton, GestureClick, GestureZoom, Label, MenuButton, Overlay, ProgressBar, ScrolledWindow,
+    Separator};
+use gtk::Align::Center;
 use gtk::EventSequenceState::Claimed;
+use gtk::Orientation::Vertical;
 use gtk::PropagationPhase::Capture;
-use id3::ErrorKind::NoTag;
-use id3::Tag;
-use id3::v1v2::write_to_path;
-use id3::Version::Id3v24;
-use log::error;
+use log::{error, warn};
 use crate::body::collection::model::Collection;
-use crate::body::merge::{KEY, MergeButton, MergeState, Query};
-use crate::body::{action_name, CHANGE_SUBTITLE, CHANGE_TITLE, HEADER_BAR_START_MERGE, next_icon, START_MERGE};
-use crate::common::check_button_dialog::check_button_dialog;
-use crate::common::constant::DESTRUCTIVE_ACTION;
-use crate::common::StyledWidget;
-use crate::common::util::Plural;
+use crate::body::merge::{journal, KEY, MergeButton, MergeState, Query};
+use crate::body::merge::duplicate::suggestions;
+use crate::body::{action_name, CHANGE_SUBTITLE, CHANGE_TITLE, HEADER_BAR_START_MERGE, next_icon, SONG, START_MERGE,
+    SUGGEST_MERGE};
+use crate::body::proposal::lookup;
+use crate::common::check_button_dialog::{append_entity, check_button_dialog};
+use crate::common::constant::{DESTRUCTIVE_ACTION, INSENSITIVE_FG, SUGGESTED_ACTION};
+use crate::common::{StyledLabelBuilder, StyledWidget};
+use crate::common::util::{or_none, Plural};
 use crate::db::get_connection;
 use crate::schema::collections::dsl::collections;
+use crate::schema::songs::{id, path};
 use crate::schema::songs::dsl::songs;
 #[allow(unused_imports)]
 use crate::song::{Song, WithPath};
+use crate::tag::{Field, WriteProgress};
+use crate::tag::writer::tag_writer;
 
 const END_MERGE: &'static str = "end_merge";
 
+fn report_skipped(dialog: &Window, entity: &str, skipped: &Vec<String>) {
+    let main_box = gtk::Box::builder().orientation(Vertical).spacing(4)
+        .margin_start(12).margin_end(12).margin_top(12).margin_bottom(12).build();
+    main_box.append(&Label::new(Some(&format!("{} skipped", skipped.len().number_plural("file"))))
+        .with_css_class("heading"));
+    main_box.append(&Label::builder().label(&format!("The {entity} tag could not be written to these files"))
+        .wrap(true).build());
+    let skipped_box = gtk::Box::builder().orientation(Vertical).spacing(4).margin_top(16).margin_bottom(16).build();
+    main_box.append(&skipped_box);
+    for message in skipped {
+        skipped_box.append(&Label::builder().label(message).xalign(0.0).wrap(true).subscript().build());
+    }
+    let close_button = Button::builder().label("Close").halign(Center).build();
+    main_box.append(&close_button);
+    close_button.connect_clicked({
+        let dialog = dialog.clone();
+        move |_| { dialog.close(); }
+    });
+    dialog.set_content(Some(&ScrolledWindow::builder().child(&main_box)
+        .propagate_natural_width(true).propagate_natural_height(true).build()));
+}
+
+fn preview<F: Fn(&Overlay, Vec<i32>) + 'static>(dialog: &Window, field: Field, entity: &str,
+    song_collections: Vec<(Song, Collection)>, on_merge: F) {
+    let main_box = gtk::Box::builder().orientation(Vertical).spacing(4)
+        .margin_start(12).margin_end(12).margin_top(12).margin_bottom(12).build();
+    let scrolled_window = ScrolledWindow::builder().child(&main_box)
+        .propagate_natural_width(true).propagate_natural_height(true).build();
+    let overlay = Overlay::builder().child(&scrolled_window).build();
+    main_box.append(&Label::new(Some("Preview merge")).with_css_class("heading"));
+    main_box.append(&Label::builder().label(&format!("{} will be set to {entity} on the checked files", field.label()))
+        .wrap(true).build());
+    let mut groups = BTreeMap::<Option<String>, Vec<(Song, Collection)>>::new();
+    for (song, collection) in song_collections {
+        groups.entry(field.value(&song)).or_default().push((song, collection));
+    }
+    let mut check_buttons = Vec::new();
+    let mut label_boxes = Vec::new();
+    let mut song_paths = Vec::new();
+    for (current, group) in groups {
+        main_box.append(&Label::builder().label(&format!("{} → {entity}", or_none(&current))).bold().xalign(0.0)
+            .ellipsized().margin_top(16).build());
+        main_box.append(&Label::builder().label(&group.len().number_plural(SONG)).xalign(0.0).subscript()
+            .name(INSENSITIVE_FG).build());
+        main_box.append(&Separator::builder().hexpand(true).build());
+        for (song, collection) in group {
+            let label_box = gtk::Box::builder().orientation(Vertical).margin_start(4).build();
+            label_box.append(&Label::builder().label(song.title_str()).xalign(0.0).ellipsized().build());
+            label_box.append(&Label::builder().label(&song.path).xalign(0.0).ellipsized().subscript()
+                .name(INSENSITIVE_FG).build());
+            let check_button = CheckButton::builder().child(&label_box).sensitive(false).build();
+            main_box.append(&check_button);
+            check_buttons.push((song.id, check_button));
+            label_boxes.push(label_box);
+            song_paths.push((&song, &collection).path());
+        }
+    }
+    let (sender, receiver) = channel::<(usize, Result<(), String>)>();
+    thread::spawn({
+        let entity = entity.to_owned();
+        move || {
+            for (i, song_path) in song_paths.into_iter().enumerate() {
+                let readable = tag_writer(&song_path).map(drop).map_err(|error| {
+                    error!("error reading tags on file [{song_path:?}] while previewing merge into [{entity}] \
+                    [{error}]");
+                    error.to_string()
+                });
+                if sender.send((i, readable)).is_err() { break; }
+            }
+        }
+    });
+    let button_box = gtk::Box::builder().spacing(16).halign(Center).margin_top(16).build();
+    main_box.append(&button_box);
+    let cancel_button = Button::builder().label("Cancel").build();
+    button_box.append(&cancel_button);
+    cancel_button.connect_clicked({
+        let dialog = dialog.clone();
+        move |_| { dialog.close(); }
+    });
+    let merge_button = Button::builder().build().with_css_class(DESTRUCTIVE_ACTION);
+    button_box.append(&merge_button);
+    let check_buttons = Rc::new(check_buttons);
+    let selected = {
+        let check_buttons = check_buttons.clone();
+        move || {
+            check_buttons.iter().filter(|(_, check_button)| { check_button.is_active() })
+                .map(|(song_id, _)| { *song_id }).collect::<Vec<_>>()
+        }
+    };
+    let read = Rc::new(Cell::new(false));
+    let update_merge_button = {
+        let merge_button = merge_button.clone();
+        let selected = selected.clone();
+        let read = read.clone();
+        move || {
+            let count = selected().len();
+            merge_button.set_label(&format!("Merge {}", count.number_plural("file")));
+            merge_button.set_sensitive(read.get() && count > 0);
+        }
+    };
+    update_merge_button();
+    for (_, check_button) in check_buttons.iter() {
+        let update_merge_button = update_merge_button.clone();
+        check_button.connect_toggled(move |_| { update_merge_button(); });
+    }
+    // tags are read in the background and each file is offered for merging once it was found readable
+    timeout_add_local(Duration::from_millis(500), {
+        let check_buttons = check_buttons.clone();
+        let update_merge_button = update_merge_button.clone();
+        move || {
+            loop {
+                match receiver.try_recv() {
+                    Err(Empty) => { return Continue; }
+                    Err(Disconnected) => {
+                        read.set(true);
+                        update_merge_button();
+                        return Break;
+                    }
+                    Ok((i, Ok(()))) => {
+                        check_buttons[i].1.set_sensitive(true);
+                        check_buttons[i].1.set_active(true);
+                    }
+                    Ok((i, Err(message))) => {
+                        label_boxes[i].append(&Label::builder().label(&message).xalign(0.0).wrap(true).subscript()
+                            .build().with_css_class("error"));
+                    }
+                }
+            }
+        }
+    });
+    merge_button.connect_clicked({
+        let overlay = overlay.clone();
+        move |merge_button| {
+            merge_button.set_sensitive(false);
+            cancel_button.set_sensitive(false);
+            on_merge(&overlay, selected());
+        }
+    });
+    dialog.set_content(Some(&overlay));
+}
+
+fn merge(this: &Rc<MergeState>, dialog: &Window, overlay: &Overlay, field: Field, entity: String, song_ids: Vec<i32>) {
+    let progress_bar = ProgressBar::builder().hexpand(true).build().osd();
+    overlay.add_overlay(&progress_bar);
+    let (sender, receiver) = channel::<WriteProgress>();
+    let string = this.entity;
+    thread::spawn(move || {
+        let song_collections = songs.inner_join(collections).filter(id.eq_any(song_ids))
+            .get_results::<(Song, Collection)>(&mut get_connection()).unwrap();
+        let mut connection = get_connection();
+        // recorded with the first file written so that a merge where every file fails leaves no history
+        let mut merge_id = None;
+        let value = Some(entity.clone());
+        let total = song_collections.len();
+        for (i, (song, collection)) in song_collections.into_iter().enumerate() {
+            let current_path = (&song, &collection).path();
+            // the file's own tag is journaled, which the database may have normalised or not caught up with
+            match tag_writer(&current_path).and_then(|mut writer| {
+                let original_value = writer.get(field);
+                writer.set(field, &value)?;
+                writer.write(&current_path)?;
+                Ok(original_value)
+            }) {
+                Ok(original_value) => {
+                    field.update_song(song.id, &value, &mut connection).unwrap();
+                    let merge_id = *merge_id
+                        .get_or_insert_with(|| { journal::record(field, &entity, &mut connection).unwrap() });
+                    journal::record_song(merge_id, song.id, original_value, &mut connection).unwrap();
+                }
+                Err(error) => {
+                    error!("error writing tags on file [{current_path:?}] while trying to set [{string}] [{entity}] \
+                    [{error}]");
+                    sender.send(WriteProgress::Failed(format!("{} [{error}]", current_path.to_str().unwrap())))
+                        .unwrap();
+                }
+            }
+            sender.send(WriteProgress::Fraction(i as f64 / total as f64)).unwrap();
+        }
+    });
+    timeout_add_local(Duration::from_millis(500), {
+        let this = this.clone();
+        let dialog = dialog.clone();
+        let mut skipped = Vec::new();
+        move || {
+            let mut merge_progress: Option<f64> = None;
+            loop {
+                match receiver.try_recv() {
+                    Err(Empty) => { break; }
+                    Err(Disconnected) => {
+                        this.end_merge();
+                        if skipped.is_empty() {
+                            dialog.close();
+                        } else {
+                            report_skipped(&dialog, string, &skipped);
+                        }
+                        return Break;
+                    }
+                    Ok(WriteProgress::Fraction(fraction)) => { merge_progress = Some(fraction); }
+                    Ok(WriteProgress::Failed(message)) => { skipped.push(message); }
+                }
+            }
+            if let Some(fraction) = merge_progress { progress_bar.set_fraction(fraction); }
+            Continue
+        }
+    });
+}
+
+fn append_proposed_names(field: Field, name: String, entities: Vec<String>, check_buttons: Vec<CheckButton>) {
+    lookup(move |release_lookup, sender| {
+        if field == Field::Album {
+            release_lookup.lookup_release("", &name, sender);
+        } else {
+            release_lookup.lookup_release(&name, "", sender);
+        }
+    }, move |releases| {
+        match releases {
+            Err(error) => { warn!("error looking up [{}] names [{error}]", field.label()); }
+            Ok(releases) => {
+                let mut proposed = Vec::<String>::new();
+                for release in releases {
+                    let name = if field == Field::Album { release.album } else { release.artist };
+                    if !entities.contains(&name) && !proposed.contains(&name) {
+                        let name_box = gtk::Box::builder().orientation(Vertical).spacing(4).build();
+                        name_box.append(&Label::builder().label(&name).xalign(0.0).build());
+                        name_box.append(&Label::builder().label(&format!("Proposed by {}", release.provider))
+                            .xalign(0.0).subscript().name(INSENSITIVE_FG).build());
+                        append_entity(&check_buttons[0], &(name_box, Some(name.clone())));
+                        proposed.push(name);
+                    }
+                }
+            }
+        }
+    });
+}
+
 impl MergeState {
     pub(in crate::body) fn new<
-        I: Fn(Vec<Option<String>>) -> Query + Send + Clone + 'static, N: Fn() -> Query + Send + Clone + 'static,
-        T: Fn(&mut Tag, &str) + Send + Clone + 'static, M: Fn(Song, &str) + Send + Clone + 'static
+        I: Fn(Vec<Option<String>>) -> Query + Send + Clone + 'static, N: Fn() -> Query + Send + Clone + 'static
     >(string: &'static str, heading: Rc<String>, title: Arc<String>, subtitle: Rc<String>, entities_box: gtk::Box,
         action_group: &SimpleActionGroup, header_bar: &HeaderBar, menu_button: &MenuButton, get_in_filter: I,
-        is_null: N, set_tag: T, merge: M) -> Rc<Self> {
+        is_null: N, field: Field) -> Rc<Self> {
         let cancel_button = Button::builder().label("Cancel").build();
         let merge_button = Button::builder().label("Merge").build().suggested_action();
         merge_button.disable();
@@ -65,83 +304,38 @@ impl MergeState {
                 }).collect::<Vec<_>>();
                 let has_none = entities.contains(&None);
                 let entities = entities.into_iter().filter_map(|it| { it }).collect::<Vec<_>>();
-                check_button_dialog(&heading, Some(&description),
+                let check_buttons = check_button_dialog(&heading, Some(&description),
                     &entities.clone().into_iter().map(|it| { (Label::new(Some(&it)), Some(it)) }).collect::<Vec<_>>(),
-                    "Merge", String::from(""), DESTRUCTIVE_ACTION, RefCell::new({
+                    "Preview", String::from(""), SUGGESTED_ACTION, RefCell::new({
                         let get_in_filter = get_in_filter.clone();
                         let entities = entities.clone();
                         let is_null = is_null.clone();
-                        let set_tag = set_tag.clone();
-                        let merge = merge.clone();
                         let this = this.clone();
-                        move |overlay: &Overlay, variant: Variant, dialog: &Window| {
-                            let progress_bar = ProgressBar::builder().hexpand(true).build().osd();
-                            overlay.add_overlay(&progress_bar);
-                            let (sender, receiver) = channel::<f64>();
-                            thread::spawn({
-                                let get_in_filter = get_in_filter.clone();
-                                let entities = entities.clone();
-                                let is_null = is_null.clone();
-                                let set_tag = set_tag.clone();
-                                let merge = merge.clone();
-                                move || {
-                                    let entity = variant.str().unwrap();
-                                    let in_filter = get_in_filter(entities.iter().filter_map(|it| {
-                                        (**it != entity).then_some(Some((**it).to_owned()))
-                                    }).collect::<Vec<_>>());
-                                    let statement = songs.inner_join(collections).into_boxed();
-                                    let song_collections = if has_none {
-                                        statement.filter(in_filter.or(is_null()))
-                                    } else {
-                                        statement.filter(in_filter)
-                                    }.get_results::<(Song, Collection)>(&mut get_connection()).unwrap();
-                                    let total = song_collections.len();
-                                    for (i, (song, collection)) in song_collections.into_iter().enumerate() {
-                                        let current_path = (&song, &collection).path();
-                                        let tag = match Tag::read_from_path(&current_path) {
-                                            Ok(tag) => { Some(tag) }
-                                            Err(error) => {
-                                                if let NoTag = error.kind {
-                                                    Some(Tag::new())
-                                                } else {
-                                                    error!("error reading tags on file [{current_path:?}] while trying \
-                                                    to set [{string}] [{entity}] [{error}]");
-                                                    None
-                                                }
-                                            }
-                                        };
-                                        if let Some(mut tag) = tag {
-                                            set_tag(&mut tag, &entity);
-                                            write_to_path(current_path, &tag, Id3v24).unwrap();
-                                            merge(song, &entity);
-                                        }
-                                        sender.send(i as f64 / total as f64).unwrap();
-                                    }
-                                }
-                            });
-                            timeout_add_local(Duration::from_millis(500), {
+                        move |_: &Overlay, variant: Variant, dialog: &Window| {
+                            let entity = String::from(variant.str().unwrap());
+                            let in_filter = get_in_filter(entities.iter().filter_map(|it| {
+                                (*it != entity).then_some(Some(it.clone()))
+                            }).collect::<Vec<_>>());
+                            let statement = songs.inner_join(collections).into_boxed();
+                            let song_collections = if has_none {
+                                statement.filter(in_filter.or(is_null()))
+                            } else {
+                                statement.filter(in_filter)
+                            }.order_by(path).get_results::<(Song, Collection)>(&mut get_connection()).unwrap();
+                            preview(dialog, field, &entity, song_collections, {
+                                let entity = entity.clone();
                                 let this = this.clone();
                                 let dialog = dialog.clone();
-                                move || {
-                                    let mut merge_progress: Option<f64> = None;
-                                    loop {
-                                        match receiver.try_recv() {
-                                            Err(Empty) => { break; }
-                                            Err(Disconnected) => {
-                                                this.end_merge();
-                                                dialog.close();
-                                                return Break;
-                                            }
-                                            Ok(fraction) => { merge_progress = Some(fraction); }
-                                        }
-                                    }
-                                    if let Some(fraction) = merge_progress { progress_bar.set_fraction(fraction); }
-                                    Continue
+                                move |overlay, song_ids| {
+                                    merge(&this, &dialog, overlay, field, entity.clone(), song_ids);
                                 }
                             });
                         }
                     }),
                 );
+                if let Some(name) = entities.first().cloned() {
+                    append_proposed_names(field, name, entities, check_buttons);
+                }
             }
         });
         let start_merge = SimpleAction::new(START_MERGE, None);
@@ -151,6 +345,13 @@ impl MergeState {
             let this = this.clone();
             move |_, _| { this.start_merge(); }
         });
+        let suggest_merge = SimpleAction::new(SUGGEST_MERGE, None);
+        action_group.remove_action(SUGGEST_MERGE);
+        action_group.add_action(&suggest_merge);
+        suggest_merge.connect_activate({
+            let this = this.clone();
+            move |_, _| { this.clone().suggest(field == Field::Album); }
+        });
         let header_bar_start_merge = SimpleAction::new(HEADER_BAR_START_MERGE, None);
         action_group.remove_action(HEADER_BAR_START_MERGE);
         action_group.add_action(&header_bar_start_merge);
@@ -214,6 +415,78 @@ impl MergeState {
             false
         });
     }
+    fn suggest(self: Rc<Self>, album: bool) {
+        let mut names = Vec::new();
+        self.iterate_rows(|row| {
+            if let Some(name) = unsafe { row.data::<Arc<String>>(KEY).map(|it| { it.as_ref().to_string() }) } {
+                names.push(name);
+            }
+            false
+        });
+        let (sender, receiver) = channel::<Vec<Vec<String>>>();
+        thread::spawn(move || { sender.send(suggestions(names, album)).unwrap(); });
+        timeout_add_local(Duration::from_millis(500), move || {
+            match receiver.try_recv() {
+                Err(Empty) => { Continue }
+                Err(Disconnected) => { Break }
+                Ok(groups) => {
+                    self.clone().show_suggestions(groups);
+                    Break
+                }
+            }
+        });
+    }
+    fn show_suggestions(self: Rc<Self>, groups: Vec<Vec<String>>) {
+        let main_box = gtk::Box::builder().orientation(Vertical).spacing(4)
+            .margin_start(12).margin_end(12).margin_top(12).margin_bottom(12).build();
+        let dialog = Window::builder().title("Duplicates").modal(true).content(&ScrolledWindow::builder()
+            .child(&main_box).propagate_natural_width(true).propagate_natural_height(true).build()).build();
+        main_box.append(&Label::new(Some(&format!("Possible duplicate {}s", self.entity.to_lowercase())))
+            .with_css_class("heading"));
+        if groups.is_empty() {
+            main_box.append(&Label::builder().label("No duplicates found").name(INSENSITIVE_FG).margin_top(16).build());
+        }
+        let groups_box = gtk::Box::builder().orientation(Vertical).margin_top(16).margin_bottom(16).build();
+        main_box.append(&groups_box);
+        for group in groups {
+            let group_button = Button::builder().child(&Label::builder().label(&group.join(" · ")).wrap(true)
+                .xalign(0.0).build()).has_frame(false).build();
+            groups_box.append(&group_button);
+            groups_box.append(&Separator::builder().hexpand(true).build());
+            group_button.connect_clicked({
+                let this = self.clone();
+                let dialog = dialog.clone();
+                move |_| {
+                    dialog.close();
+                    this.select_group(&group);
+                    this.merge_button.emit_clicked();
+                }
+            });
+        }
+        let close_button = Button::builder().label("Close").halign(Center).build();
+        main_box.append(&close_button);
+        close_button.connect_clicked({
+            let dialog = dialog.clone();
+            move |_| { dialog.close(); }
+        });
+        dialog.present();
+    }
+    fn select_group(&self, group: &Vec<String>) {
+        self.start_merge();
+        self.iterate_rows(|row| {
+            let in_group = unsafe { row.data::<Arc<String>>(KEY).map(|it| { it.as_ref().to_string() }) }
+                .map(|name| { group.contains(&name) }).unwrap_or(false);
+            let selected = self.selected_for_merge.borrow().contains(&row);
+            if in_group && !selected {
+                self.select_row_for_merge(&row);
+            } else if !in_group && selected {
+                self.selected_for_merge.borrow_mut().remove(&row);
+                row.last_child().and_downcast::<CheckButton>().unwrap().set_active(false);
+            }
+            false
+        });
+        self.update_selected_count();
+    }
     fn update_selected_count(&self) {
         let count = self.selected_for_merge.borrow().len();
         self.entities_box.activate_action(&action_name(CHANGE_SUBTITLE),
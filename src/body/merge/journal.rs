@@ -0,0 +1,58 @@
+use std::sync::mpsc::Sender;
+use diesel::{Connection, delete, ExpressionMethods, insert_into, QueryDsl, QueryResult, RunQueryDsl,
+    SqliteConnection};
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use log::info;
+use crate::db::get_connection;
+use crate::schema::merge_songs::{merge_id, original, song_id};
+use crate::schema::merge_songs::dsl::merge_songs;
+use crate::schema::merges::{field, id, value};
+use crate::schema::merges::dsl::merges;
+use crate::schema::songs;
+use crate::song::Song;
+use crate::tag::{Field, write_song_edits, WriteProgress};
+
+#[derive(diesel::Queryable, diesel::Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::merges)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Merge {
+    pub id: i32,
+    pub field: crate::tag::Field,
+    pub value: String,
+}
+
+pub(in crate::body) fn record(merge_field: Field, merge_value: &str,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> QueryResult<i32> {
+    insert_into(merges).values((field.eq(merge_field), value.eq(merge_value))).returning(id).get_result(connection)
+}
+
+pub(in crate::body) fn record_song(merge_id_int: i32, song_id_int: i32, original_value: Option<String>,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> QueryResult<usize> {
+    insert_into(merge_songs).values((merge_id.eq(merge_id_int), song_id.eq(song_id_int), original.eq(original_value)))
+        .execute(connection)
+}
+
+pub(in crate::body) fn history() -> Vec<Merge> {
+    merges.order_by(id.desc()).get_results::<Merge>(&mut get_connection()).unwrap()
+}
+
+pub(in crate::body) fn originals(merge: &Merge) -> Vec<Option<String>> {
+    merge_songs.filter(merge_id.eq(merge.id)).select(original).get_results::<Option<String>>(&mut get_connection())
+        .unwrap()
+}
+
+// songs changed since the merge keep their tags, their paths are returned
+pub(in crate::body) fn undo(merge: &Merge, sender: &Sender<WriteProgress>) -> anyhow::Result<Vec<String>> {
+    get_connection().transaction(|connection| {
+        let (unchanged, changed) = merge_songs.inner_join(songs::table).filter(merge_id.eq(merge.id))
+            .select((songs::all_columns, original)).get_results::<(Song, Option<String>)>(connection)?.into_iter()
+            .partition::<Vec<_>, _>(|(song, _)| { merge.field.value(song).as_ref() == Some(&merge.value) });
+        let song_edits = unchanged.into_iter().map(|(song, original_value)| {
+            (song.id, vec![(merge.field, original_value)])
+        }).collect::<Vec<_>>();
+        write_song_edits(&song_edits, sender, connection)?;
+        delete(merges.find(merge.id)).execute(connection)?;
+        info!("undid merge [{merge:?}] of [{}] songs, kept [{}] changed since", song_edits.len(), changed.len());
+        anyhow::Ok(changed.into_iter().map(|(song, _)| { song.path }).collect())
+    })
+}
@@ -8,11 +8,15 @@ use diesel::dsl::InnerJoinQuerySource;
 use diesel::sql_types::Bool;
 use diesel::sqlite::Sqlite;
 use gtk::{Button, MenuButton};
-use crate::body::{action_name, START_MERGE};
+use crate::body::{action_name, START_MERGE, SUGGEST_MERGE};
+use crate::common::state::State;
 use crate::schema::collections::dsl::collections;
 use crate::schema::songs::dsl::songs;
 
 mod r#impl;
+mod duplicate;
+mod journal;
+pub mod history;
 
 pub(super) struct MergeState {
     entity: &'static str,
@@ -26,7 +30,7 @@ pub(super) struct MergeState {
 
 pub(super) const KEY: &'static str = "key";
 
-type Query = Box<dyn BoxableExpression<InnerJoinQuerySource<songs, collections>, Sqlite, SqlType=Bool>>;
+pub(super) type Query = Box<dyn BoxableExpression<InnerJoinQuerySource<songs, collections>, Sqlite, SqlType=Bool>>;
 
 trait MergeButton {
     fn disable(&self);
@@ -39,14 +43,27 @@ impl MergeButton for Button {
     }
 }
 
-pub fn add_menu_merge_button(entity: &str, menu_button: &MenuButton, popover_box: &gtk::Box) -> Rc<String> {
+pub fn add_menu_merge_button(entity: &str, state: Rc<State>, menu_button: &MenuButton, popover_box: &gtk::Box)
+    -> Rc<String> {
     let heading = format!("Merge {entity}s");
     let merge_menu_button = Button::builder().label(&heading).build();
-    let menu_button = menu_button.clone();
-    merge_menu_button.connect_clicked(move |merge_menu_button| {
-        merge_menu_button.activate_action(&action_name(START_MERGE), None).unwrap();
-        menu_button.popdown();
+    merge_menu_button.connect_clicked({
+        let menu_button = menu_button.clone();
+        move |merge_menu_button| {
+            merge_menu_button.activate_action(&action_name(START_MERGE), None).unwrap();
+            menu_button.popdown();
+        }
     });
     popover_box.append(&merge_menu_button);
+    let suggest_button = Button::builder().label("Find duplicates").build();
+    suggest_button.connect_clicked({
+        let menu_button = menu_button.clone();
+        move |suggest_button| {
+            suggest_button.activate_action(&action_name(SUGGEST_MERGE), None).unwrap();
+            menu_button.popdown();
+        }
+    });
+    popover_box.append(&suggest_button);
+    history::append_buttons(state, menu_button, popover_box);
     Rc::new(heading)
 }
@@ -3,14 +3,20 @@ use std::sync::Arc;
 use adw::{HeaderBar, NavigationPage, WindowTitle};
 use adw::gio::{SimpleAction, SimpleActionGroup};
 use adw::prelude::*;
-use gtk::{Image, Label, MenuButton, Popover, ScrolledWindow, Widget};
+use gtk::{Button, Image, Label, MenuButton, Popover, ScrolledWindow, Widget};
 use gtk::Orientation::Vertical;
+use crate::body::facet::Facet;
 use crate::common::state::State;
+use crate::queue::{enqueue, Enqueue};
 
 pub mod collection;
-mod merge;
+pub mod merge;
 pub mod artists;
 pub mod download;
+pub mod search;
+pub mod facet;
+pub mod tag_editor;
+pub mod proposal;
 
 #[derive(diesel::Queryable, diesel::Selectable, Debug)]
 #[diesel(table_name = crate::schema::bodies)]
@@ -22,12 +28,17 @@ pub struct BodyTable {
     pub params: String,
 }
 
-#[derive(Debug, diesel_derive_enum::DbEnum)]
+#[derive(Debug, PartialEq, diesel_derive_enum::DbEnum)]
 pub enum BodyType {
     Artists,
     Albums,
     Songs,
     Collections,
+    Search,
+    Genres,
+    Years,
+    TagEditor,
+    MergeHistory,
 }
 
 fn next_icon() -> Image {
@@ -77,6 +88,14 @@ impl Body {
         let popover_box = gtk::Box::builder().orientation(Vertical).build();
         let menu_button = MenuButton::builder().icon_name("open-menu-symbolic").tooltip_text("Menu")
             .popover(&Popover::builder().child(&popover_box).build()).build();
+        if !matches!(body_type, BodyType::Search) {
+            popover_box.append(&search::button(state.clone(), &menu_button));
+        }
+        for (facet, facet_body_type) in [(Facet::Genre, BodyType::Genres), (Facet::Year, BodyType::Years)] {
+            if body_type != facet_body_type {
+                popover_box.append(&facet::button(facet, state.clone(), &menu_button));
+            }
+        }
         popover_box.append(&collection::button::create(state, &menu_button));
         let child = gtk::Box::builder().orientation(Vertical).build();
         let window_title = WindowTitle::builder().title(title).build();
@@ -134,6 +153,7 @@ fn create_navigation_page(child: &gtk::Box, tag: &str, params: Vec<Option<Arc<St
 
 const NAVIGATION_PAGE: &'static str = "navigation_page";
 const START_MERGE: &'static str = "start_merge";
+const SUGGEST_MERGE: &'static str = "suggest_merge";
 const HEADER_BAR_START_MERGE: &'static str = "header_bar_start_merge";
 
 fn action_name(name: &str) -> String {
@@ -144,3 +164,24 @@ fn handle_render<R: Fn() + 'static>(render: R, rerender: SimpleAction) {
     render();
     rerender.connect_activate(move |_, _| { render(); });
 }
+
+fn append_queue_buttons<F: Fn() -> Vec<i32> + Clone + 'static>(entity: Option<&str>, popover_box: &gtk::Box,
+    menu_button: &MenuButton, song_ids: F) {
+    let (next_label, last_label) = if let Some(entity) = entity {
+        (format!("Play {entity} next"), format!("Add {entity} to queue"))
+    } else {
+        (String::from("Play next"), String::from("Add to queue"))
+    };
+    for (label, enqueue_type) in [(next_label, Enqueue::Next), (last_label, Enqueue::Last)] {
+        let queue_button = Button::builder().label(label).build();
+        popover_box.append(&queue_button);
+        queue_button.connect_clicked({
+            let menu_button = menu_button.clone();
+            let song_ids = song_ids.clone();
+            move |_| {
+                enqueue(song_ids(), enqueue_type);
+                menu_button.popdown();
+            }
+        });
+    }
+}
@@ -0,0 +1,69 @@
+use std::cell::Cell;
+use std::sync::mpsc::{channel, Sender, TryRecvError::*};
+use std::time::Duration;
+use adw::glib::{ControlFlow::*, timeout_add_local};
+use adw::prelude::*;
+use gtk::Label;
+use gtk::Orientation::Vertical;
+use once_cell::sync::Lazy;
+use metadata_fetch::{ProposedRelease, ReleaseLookup};
+use musicbrainz::MusicBrainz;
+use crate::common::StyledLabelBuilder;
+use crate::common::constant::INSENSITIVE_FG;
+use crate::common::util::Plural;
+use crate::song::Song;
+use crate::tag::{Field, TagEdit};
+
+static MUSICBRAINZ: Lazy<MusicBrainz> = Lazy::new(|| { MusicBrainz::default() });
+
+pub(in crate::body) type Releases = anyhow::Result<Vec<ProposedRelease>>;
+
+pub(in crate::body) fn lookup(start: impl FnOnce(&'static dyn ReleaseLookup, Sender<Releases>),
+    on_releases: impl FnOnce(Releases) + 'static) {
+    let (sender, receiver) = channel::<Releases>();
+    start(&*MUSICBRAINZ, sender);
+    let on_releases = Cell::new(Some(on_releases));
+    timeout_add_local(Duration::from_millis(500), move || {
+        match receiver.try_recv() {
+            Err(Empty) => { Continue }
+            Err(Disconnected) => { Break }
+            Ok(releases) => {
+                on_releases.take().unwrap()(releases);
+                Break
+            }
+        }
+    });
+}
+
+pub(in crate::body) fn release_box(release: &ProposedRelease) -> gtk::Box {
+    let gtk_box = gtk::Box::builder().orientation(Vertical).spacing(4).hexpand(true).margin_start(4).build();
+    gtk_box.append(&Label::builder().label(&release.album).bold().wrap(true).build());
+    gtk_box.append(&Label::builder().label(&release.artist).wrap(true).build());
+    let details = release.year.map(|it| { it.to_string() }).into_iter()
+        .chain([release.tracks.len().number_plural("track")]).collect::<Vec<_>>().join(" · ");
+    gtk_box.append(&Label::builder().label(&details).wrap(true).subscript().name(INSENSITIVE_FG).build());
+    gtk_box.append(&Label::builder().label(release.provider).subscript().name(INSENSITIVE_FG).build());
+    gtk_box
+}
+
+pub(in crate::body) fn proposed_edits(release: &ProposedRelease, songs: &[Song]) -> Vec<(i32, TagEdit)> {
+    let multi_disc = release.tracks.iter().any(|track| { track.album_volume != release.tracks[0].album_volume });
+    songs.iter().enumerate().map(|(i, song)| {
+        let track = song.track_number.and_then(|number| {
+            release.tracks.iter().find(|track| {
+                track.track_number == Some(number)
+                    && (!multi_disc || track.album_volume == song.album_volume.or(Some(1)))
+            })
+        }).or_else(|| { (songs.len() == release.tracks.len()).then(|| { &release.tracks[i] }) });
+        let mut edit = vec![(Field::AlbumArtist, Some(release.artist.clone())),
+            (Field::Album, Some(release.album.clone()))];
+        if let Some(year) = release.year { edit.push((Field::Year, Some(year.to_string()))); }
+        if let Some(track) = track {
+            edit.push((Field::Title, Some(track.title.clone())));
+            edit.push((Field::Artist, Some(track.artist.clone().unwrap_or_else(|| { release.artist.clone() }))));
+            edit.push((Field::TrackNumber, track.track_number.map(|it| { it.to_string() })));
+            if multi_disc { edit.push((Field::AlbumVolume, track.album_volume.map(|it| { it.to_string() }))); }
+        }
+        (song.id, edit)
+    }).collect()
+}
@@ -0,0 +1,218 @@
+use std::rc::Rc;
+use std::sync::Arc;
+use adw::glib::markup_escape_text;
+use adw::NavigationPage;
+use adw::prelude::*;
+use diesel::{QueryableByName, RunQueryDsl, sql_query};
+use diesel::sql_types::{Integer, Nullable, Text};
+use gtk::{Button, GestureClick, Label, MenuButton, SearchEntry, Separator};
+use gtk::Orientation::Vertical;
+use log::warn;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use crate::body::{ALBUM, ARTIST, Body, BodyType, handle_render, PARAMS, SONG};
+use crate::body::download::albums::albums_page;
+use crate::body::download::handle_scroll;
+use crate::body::download::songs::songs_page;
+use crate::common::StyledLabelBuilder;
+use crate::common::constant::INSENSITIVE_FG;
+use crate::common::state::State;
+use crate::common::util::{or_none, PathString, Plural};
+use crate::db::get_connection;
+use crate::queue::follow_album;
+use crate::song::{join_path, primary_artist, WithImage};
+
+const SEARCH: &'static str = "Search";
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_END: char = '\u{3}';
+
+#[derive(QueryableByName)]
+struct SearchResult {
+    #[diesel(sql_type = Text)]
+    path: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    title: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    artist: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    album: Option<String>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    album_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    album_cover: Option<String>,
+    #[diesel(sql_type = Text)]
+    collection_path: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    lyrics_snippet: Option<String>,
+}
+
+impl SearchResult {
+    fn title_str(&self) -> &str {
+        self.title.as_deref().unwrap_or(self.path.to_path().file_name().unwrap().to_str().unwrap())
+    }
+}
+
+fn search(tokens: &Vec<String>) -> Vec<SearchResult> {
+    let match_query = tokens.iter().map(|token| { format!("\"{}\"*", token.replace('"', "\"\"")) })
+        .collect::<Vec<_>>().join(" ");
+    sql_query("select songs.path, songs.title, songs.artist, songs.album, songs.album_id, \
+        albums.cover as album_cover, collections.path as collection_path, \
+        snippet(songs_fts, 4, char(2), char(3), '…', 12) as lyrics_snippet \
+        from songs_fts inner join songs on songs.id = songs_fts.rowid \
+        inner join collections on collections.id = songs.collection_id \
+        left join albums on albums.id = songs.album_id \
+        where songs_fts match ? order by rank limit 500")
+        .bind::<Text, _>(match_query).load::<SearchResult>(&mut get_connection()).unwrap_or_else(|error| {
+        warn!("error searching for [{tokens:?}] [{error}]");
+        Vec::new()
+    })
+}
+
+// folded like the unicode61 tokenizer of songs_fts, which ignores case and diacritics
+fn fold(string: &str) -> String {
+    string.nfd().filter(|c| { !is_combining_mark(*c) }).collect::<String>().to_lowercase()
+}
+
+fn matches(tokens: &Vec<String>, fields: &[&Option<String>]) -> bool {
+    let fields = fields.iter().filter_map(|field| { field.as_deref().map(fold) }).collect::<Vec<_>>();
+    tokens.iter().all(|token| { fields.iter().any(|field| { field.contains(token) }) })
+}
+
+fn highlighted_line(lyrics_snippet: &str) -> Option<String> {
+    lyrics_snippet.lines().find(|line| { line.contains(HIGHLIGHT_START) }).map(|line| {
+        line.split(|it| { it == HIGHLIGHT_START || it == HIGHLIGHT_END }).enumerate().map(|(i, part)| {
+            let part = markup_escape_text(part);
+            if i % 2 == 1 { format!("<b>{part}</b>") } else { part.to_string() }
+        }).collect()
+    })
+}
+
+fn section(results_box: &gtk::Box, heading: &str, count: usize) {
+    results_box.append(&Label::builder().label(&count.number_plural(heading)).xalign(0.0).margin_start(8)
+        .margin_top(16).margin_bottom(4).build());
+    results_box.append(&Separator::builder().build());
+}
+
+fn result_row<F: Fn() + 'static>(results_box: &gtk::Box, title: &str, subtitle: &str, on_click: F) -> gtk::Box {
+    let row = gtk::Box::builder().orientation(Vertical).margin_start(8).margin_end(8).margin_top(8)
+        .margin_bottom(8).build();
+    results_box.append(&row);
+    results_box.append(&Separator::builder().build());
+    row.append(&Label::builder().label(title).ellipsized().build());
+    row.append(&Label::builder().label(subtitle).ellipsized().subscript().name(INSENSITIVE_FG).build());
+    let gesture_click = GestureClick::new();
+    gesture_click.connect_released(move |_, _, _, _| { on_click(); });
+    row.add_controller(gesture_click);
+    row
+}
+
+pub fn search_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_adjustment: Option<f64>)
+    -> NavigationPage {
+    let query = params.first().cloned().flatten();
+    let body = Body::new(SEARCH, state.clone(), None, params, BodyType::Search);
+    let search_entry = SearchEntry::builder().hexpand(true).placeholder_text("Artists, albums, songs and lyrics")
+        .text(query.as_deref().map(String::as_str).unwrap_or("")).build();
+    body.header_bar.set_title_widget(Some(&search_entry));
+    search_entry.connect_search_changed({
+        let navigation_page = body.navigation_page.clone();
+        let rerender = body.rerender.clone();
+        move |search_entry| {
+            unsafe { navigation_page.set_data(PARAMS, vec![Some(Arc::new(search_entry.text().to_string()))]); }
+            rerender.activate(None);
+        }
+    });
+    let adjustment = body.scrolled_window.vadjustment();
+    let render = move || {
+        let tokens = search_entry.text().split_whitespace().map(fold).collect::<Vec<_>>();
+        let results_box = gtk::Box::builder().orientation(Vertical).build();
+        let results = if tokens.is_empty() { Vec::new() } else { search(&tokens) };
+        let mut artists = Vec::<(Option<String>, &SearchResult)>::new();
+        let mut albums = Vec::<&SearchResult>::new();
+        for result in &results {
+            // artists are listed by primary artist, the key their albums page filters by
+            let primary = result.artist.as_deref().map(primary_artist).map(String::from);
+            if matches(&tokens, &[&primary]) && !artists.iter().any(|(it, _)| { *it == primary }) {
+                artists.push((primary, result));
+            }
+            if matches(&tokens, &[&result.album])
+                && !albums.iter().any(|it| { it.album_id == result.album_id }) {
+                albums.push(result);
+            }
+        }
+        let songs = results.iter().filter_map(|result| {
+            let lyrics_line = result.lyrics_snippet.as_deref().and_then(highlighted_line);
+            (matches(&tokens, &[&result.title, &result.artist, &result.album]) || lyrics_line.is_some())
+                .then_some((result, lyrics_line))
+        }).collect::<Vec<_>>();
+        let found = !artists.is_empty() || !albums.is_empty() || !songs.is_empty();
+        if !artists.is_empty() {
+            section(&results_box, ARTIST, artists.len());
+            for (primary, result) in artists {
+                let logo = join_path(&result.collection_path, &result.path).logo();
+                let artist_string = primary.clone().map(Arc::new);
+                result_row(&results_box, or_none(&primary), ARTIST, {
+                    let state = state.clone();
+                    move || {
+                        state.navigation_view.push(&albums_page(vec![logo.to_str()
+                            .map(|it| { Arc::new(String::from(it)) }), artist_string.clone()], state.clone(), None));
+                    }
+                });
+            }
+        }
+        if !albums.is_empty() {
+            section(&results_box, ALBUM, albums.len());
+            for result in albums {
+                let cover = if let Some(album_cover) = &result.album_cover {
+                    join_path(&result.collection_path, album_cover)
+                } else {
+                    join_path(&result.collection_path, &result.path).cover()
+                };
+                let artist_string = result.artist.clone().map(Arc::new);
+                let album_string = result.album.clone().map(Arc::new);
+                let album_id = result.album_id.map(|it| { Arc::new(it.to_string()) });
+                result_row(&results_box, or_none(&result.album), or_none(&result.artist), {
+                    let state = state.clone();
+                    move || {
+                        state.navigation_view.push(&songs_page(vec![cover.to_str()
+                            .map(|it| { Arc::new(it.to_owned()) }), artist_string.clone(), album_string.clone(),
+                            album_id.clone()], state.clone(), None));
+                    }
+                });
+            }
+        }
+        if !songs.is_empty() {
+            section(&results_box, SONG, songs.len());
+            for (result, lyrics_line) in songs {
+                let path = join_path(&result.collection_path, &result.path);
+                let row = result_row(&results_box, result.title_str(),
+                    &format!("{} - {}", or_none(&result.artist), or_none(&result.album)), {
+                        let state = state.clone();
+                        move || {
+                            follow_album();
+                            state.window_actions.song_selected.activate(path.to_str().unwrap());
+                        }
+                    });
+                if let Some(lyrics_line) = lyrics_line {
+                    row.append(&Label::builder().label(&lyrics_line).use_markup(true).ellipsized().build());
+                }
+            }
+        }
+        if !tokens.is_empty() && !found {
+            results_box.append(&Label::builder().label("No results").name(INSENSITIVE_FG).margin_top(12).build());
+        }
+        body.scrolled_window.set_child(Some(&results_box));
+    };
+    handle_scroll(scroll_adjustment, adjustment);
+    handle_render(render, body.rerender);
+    body.navigation_page
+}
+
+pub(in crate::body) fn button(state: Rc<State>, menu_button: &MenuButton) -> Button {
+    let search_button = Button::builder().label(SEARCH).build();
+    let menu_button = menu_button.clone();
+    search_button.connect_clicked(move |_| {
+        state.navigation_view.push(&search_page(vec![None], state.clone(), None));
+        menu_button.popdown();
+    });
+    search_button
+}
@@ -0,0 +1,275 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::mpsc::{channel, TryRecvError::*};
+use std::thread;
+use std::time::Duration;
+use adw::{NavigationPage, Window};
+use adw::glib::{ControlFlow::*, timeout_add_local, Variant};
+use adw::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use gtk::{Button, Entry, InputPurpose, Label, MenuButton, Overlay, ProgressBar, ScrolledWindow, Separator, TextView,
+    WrapMode};
+use gtk::Align::Center;
+use gtk::Orientation::Vertical;
+use log::error;
+use crate::body::{action_name, Body, BodyType, RERENDER, SONG};
+use crate::body::download::handle_scroll;
+use crate::body::proposal::{lookup, proposed_edits, release_box};
+use crate::common::check_button_dialog::check_button_dialog;
+use crate::common::{StyledLabelBuilder, StyledWidget};
+use crate::common::constant::{DESTRUCTIVE_ACTION, INSENSITIVE_FG, NONE, SUGGESTED_ACTION};
+use crate::common::state::State;
+use crate::common::util::{or_none, Plural};
+use crate::db::get_connection;
+use crate::schema::songs::{album_volume, id, track_number};
+use crate::schema::songs::dsl::songs;
+use crate::song::Song;
+use crate::tag::{changes, Field, FIELDS, TagEdit, write_tags, WriteProgress};
+
+const EDIT_TAGS: &'static str = "Edit tags";
+
+fn first_line(text: &str) -> String {
+    format!("{}…", text.lines().next().unwrap_or(""))
+}
+
+fn shared_value(selected: &[Song], field: Field) -> Option<String> {
+    let values = selected.iter().map(|song| { field.value(song) }).collect::<HashSet<_>>();
+    if values.len() == 1 { values.into_iter().next().flatten() } else { None }
+}
+
+fn preview_dialog(state: Rc<State>, song_edits: Vec<(i32, TagEdit)>) {
+    let main_box = gtk::Box::builder().orientation(Vertical).spacing(4)
+        .margin_start(12).margin_end(12).margin_top(12).margin_bottom(12).build();
+    let scrolled_window = ScrolledWindow::builder().child(&main_box)
+        .propagate_natural_width(true).propagate_natural_height(true).build();
+    let overlay = Overlay::builder().child(&scrolled_window).build();
+    let dialog = Window::builder().title(EDIT_TAGS).modal(true).transient_for(&state.window).content(&overlay)
+        .build();
+    main_box.append(&Label::new(Some("Preview changes")).with_css_class("heading"));
+    let changes_box = gtk::Box::builder().orientation(Vertical).margin_top(16).margin_bottom(16).build();
+    main_box.append(&changes_box);
+    let mut change_count = 0;
+    let edits = song_edits.iter().cloned().collect::<HashMap<_, _>>();
+    for song in songs.filter(id.eq_any(edits.keys().copied().collect::<Vec<_>>()))
+        .order_by((album_volume, track_number, id)).get_results::<Song>(&mut get_connection()).unwrap() {
+        let song_changes = changes(&song, &edits[&song.id]);
+        if song_changes.is_empty() { continue; }
+        change_count += song_changes.len();
+        changes_box.append(&Label::builder().label(song.title_str()).bold().xalign(0.0).margin_top(8).build());
+        for (field, current, value) in song_changes {
+            let (current, value) = if field == Field::Lyrics {
+                (current.map(|it| { first_line(&it) }), value.map(|it| { first_line(&it) }))
+            } else {
+                (current, value)
+            };
+            changes_box.append(&Label::builder().label(&format!("{}: {} → {}", field.label(), or_none(&current),
+                or_none(&value))).xalign(0.0).ellipsized().subscript().build());
+        }
+        changes_box.append(&Separator::builder().hexpand(true).margin_top(8).build());
+    }
+    if change_count == 0 {
+        changes_box.append(&Label::builder().label("Nothing to change").name(INSENSITIVE_FG).build());
+    }
+    let error_label = Label::builder().wrap(true).visible(false).build().with_css_class("error");
+    main_box.append(&error_label);
+    let button_box = gtk::Box::builder().spacing(16).halign(Center).build();
+    main_box.append(&button_box);
+    let cancel_button = Button::builder().label("Cancel").build();
+    button_box.append(&cancel_button);
+    cancel_button.connect_clicked({
+        let dialog = dialog.clone();
+        move |_| { dialog.close(); }
+    });
+    let write_button = Button::builder().label(format!("Write {}", change_count.number_plural("change")))
+        .sensitive(change_count > 0).build().with_css_class(DESTRUCTIVE_ACTION);
+    button_box.append(&write_button);
+    write_button.connect_clicked({
+        let dialog = dialog.clone();
+        move |write_button| {
+            write_button.set_sensitive(false);
+            cancel_button.set_sensitive(false);
+            let progress_bar = ProgressBar::builder().hexpand(true).build().osd();
+            overlay.add_overlay(&progress_bar);
+            let (sender, receiver) = channel::<WriteProgress>();
+            thread::spawn({
+                let song_edits = song_edits.clone();
+                move || {
+                    if let Err(error) = write_tags(&song_edits, &sender) {
+                        let song_ids = song_edits.iter().map(|(song_id, _)| { *song_id }).collect::<Vec<_>>();
+                        error!("error writing tags to songs [{song_ids:?}] [{error}]");
+                        sender.send(WriteProgress::Failed(error.to_string())).unwrap();
+                    }
+                }
+            });
+            let failed = Cell::new(false);
+            timeout_add_local(Duration::from_millis(500), {
+                let overlay = overlay.clone();
+                let state = state.clone();
+                let dialog = dialog.clone();
+                let cancel_button = cancel_button.clone();
+                let error_label = error_label.clone();
+                move || {
+                    loop {
+                        match receiver.try_recv() {
+                            Err(Empty) => { break; }
+                            Err(Disconnected) => {
+                                overlay.remove_overlay(&progress_bar);
+                                if failed.get() {
+                                    cancel_button.set_sensitive(true);
+                                } else {
+                                    dialog.close();
+                                    state.navigation_view.pop();
+                                    if let Some(navigation_page) = state.navigation_view.visible_page() {
+                                        navigation_page.activate_action(&action_name(RERENDER), None).ok();
+                                    }
+                                }
+                                return Break;
+                            }
+                            Ok(WriteProgress::Fraction(fraction)) => { progress_bar.set_fraction(fraction); }
+                            Ok(WriteProgress::Failed(message)) => {
+                                failed.set(true);
+                                error_label.set_label(&format!("No tags were changed: {message}"));
+                                error_label.set_visible(true);
+                            }
+                        }
+                    }
+                    Continue
+                }
+            });
+        }
+    });
+    dialog.present();
+}
+
+pub fn tag_editor_page(params: Vec<Option<Arc<String>>>, state: Rc<State>, scroll_adjustment: Option<f64>)
+    -> NavigationPage {
+    let song_ids = params.iter().filter_map(|it| { it.as_ref().and_then(|it| { it.parse::<i32>().ok() }) })
+        .collect::<Vec<_>>();
+    let selected = Rc::new(songs.filter(id.eq_any(&song_ids)).order_by((album_volume, track_number, id))
+        .get_results::<Song>(&mut get_connection()).unwrap());
+    let body = Body::new(EDIT_TAGS, state.clone(), None, params, BodyType::TagEditor);
+    body.window_title.set_subtitle(&selected.len().number_plural(SONG));
+    let form = gtk::Box::builder().orientation(Vertical).spacing(4)
+        .margin_start(12).margin_end(12).margin_top(12).margin_bottom(12).build();
+    let mut editors = Vec::<(_, Rc<Cell<bool>>, Box<dyn Fn() -> String>)>::new();
+    for field in FIELDS {
+        let values = selected.iter().map(|song| { field.value(song) }).collect::<HashSet<_>>();
+        let placeholder = if values.len() > 1 { "Multiple values" } else { NONE };
+        let shared = if values.len() == 1 { values.into_iter().next().flatten() } else { None };
+        form.append(&Label::builder().label(field.label()).xalign(0.0).margin_top(8).subscript().name(INSENSITIVE_FG)
+            .build());
+        let edited = Rc::new(Cell::new(false));
+        if field == Field::Lyrics {
+            let text_view = TextView::builder().wrap_mode(WrapMode::Word).top_margin(8).bottom_margin(8)
+                .left_margin(8).right_margin(8).build();
+            text_view.buffer().set_text(shared.as_deref().unwrap_or(""));
+            text_view.buffer().connect_changed({
+                let edited = edited.clone();
+                move |_| { edited.set(true); }
+            });
+            form.append(&ScrolledWindow::builder().child(&text_view).min_content_height(160).build()
+                .with_css_class("card"));
+            editors.push((field, edited, Box::new(move || {
+                let buffer = text_view.buffer();
+                buffer.text(&buffer.start_iter(), &buffer.end_iter(), false).to_string()
+            })));
+        } else {
+            let entry = Entry::builder().text(shared.as_deref().unwrap_or("")).placeholder_text(placeholder).build();
+            if field.numeric() { entry.set_input_purpose(InputPurpose::Digits); }
+            entry.connect_changed({
+                let edited = edited.clone();
+                move |_| { edited.set(true); }
+            });
+            form.append(&entry);
+            editors.push((field, edited, Box::new(move || { entry.text().to_string() })));
+        }
+    }
+    let error_label = Label::builder().wrap(true).xalign(0.0).visible(false).build().with_css_class("error");
+    form.append(&error_label);
+    let button_box = gtk::Box::builder().spacing(16).halign(Center).margin_top(12).build();
+    form.append(&button_box);
+    let lookup_button = Button::builder().label("Look up release").build();
+    button_box.append(&lookup_button);
+    lookup_button.connect_clicked({
+        let state = state.clone();
+        let error_label = error_label.clone();
+        move |lookup_button| {
+            lookup_button.set_sensitive(false);
+            let artist = shared_value(&selected, Field::AlbumArtist).or(shared_value(&selected, Field::Artist));
+            let album = shared_value(&selected, Field::Album);
+            let durations = selected.iter().map(|song| { Duration::from_nanos(song.duration as u64) })
+                .collect::<Vec<_>>();
+            lookup(move |release_lookup, sender| {
+                if let Some(album) = album {
+                    release_lookup.lookup_release(artist.as_deref().unwrap_or(""), &album, sender);
+                } else {
+                    release_lookup.lookup_release_by_durations(&durations, sender);
+                }
+            }, {
+                let lookup_button = lookup_button.clone();
+                let state = state.clone();
+                let error_label = error_label.clone();
+                let selected = selected.clone();
+                move |releases| {
+                    lookup_button.set_sensitive(true);
+                    match releases {
+                        Err(error) => {
+                            error!("error looking up release [{error}]");
+                            error_label.set_label(&format!("Looking up the release failed: {error}"));
+                            error_label.set_visible(true);
+                        }
+                        Ok(releases) if releases.is_empty() => {
+                            error_label.set_label("No matching release found");
+                            error_label.set_visible(true);
+                        }
+                        Ok(releases) => {
+                            error_label.set_visible(false);
+                            check_button_dialog("Choose the release", None, &releases.iter().enumerate()
+                                .map(|(i, release)| { (release_box(release), Some(Rc::new(i as i32))) })
+                                .collect::<Vec<_>>(), "Preview", -1, SUGGESTED_ACTION,
+                                RefCell::new(move |_: &Overlay, variant: Variant, dialog: &Window| {
+                                    dialog.close();
+                                    preview_dialog(state.clone(), proposed_edits(
+                                        &releases[variant.get::<i32>().unwrap() as usize], &selected));
+                                }),
+                            );
+                        }
+                    }
+                }
+            });
+        }
+    });
+    let preview_button = Button::builder().label("Preview changes").build().suggested_action();
+    button_box.append(&preview_button);
+    preview_button.connect_clicked(move |_| {
+        let edit = editors.iter().filter(|(_, edited, _)| { edited.get() }).map(|(field, _, editor)| {
+            (*field, Some(editor().trim().to_owned()).filter(|it| { !it.is_empty() }))
+        }).collect::<TagEdit>();
+        if let Some((field, _)) = edit.iter().find(|(field, value)| {
+            field.numeric() && value.as_ref().map(|it| { it.parse::<i32>().is_err() }).unwrap_or(false)
+        }) {
+            error_label.set_label(&format!("{} must be a number", field.label()));
+            error_label.set_visible(true);
+        } else {
+            error_label.set_visible(false);
+            preview_dialog(state.clone(), song_ids.iter().map(|song_id| { (*song_id, edit.clone()) }).collect());
+        }
+    });
+    body.scrolled_window.set_child(Some(&form));
+    handle_scroll(scroll_adjustment, body.scrolled_window.vadjustment());
+    body.navigation_page
+}
+
+pub(in crate::body) fn button(label: &str, state: Rc<State>, menu_button: &MenuButton,
+    song_ids: impl Fn() -> Vec<i32> + 'static) -> Button {
+    let tag_editor_button = Button::builder().label(label).build();
+    let menu_button = menu_button.clone();
+    tag_editor_button.connect_clicked(move |_| {
+        let params = song_ids().into_iter().map(|it| { Some(Arc::new(it.to_string())) }).collect();
+        state.navigation_view.push(&tag_editor_page(params, state.clone(), None));
+        menu_button.popdown();
+    });
+    tag_editor_button
+}
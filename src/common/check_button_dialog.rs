@@ -24,6 +24,13 @@ fn check_button<T: ToVariant>(entity: &(impl IsA<Widget>, Option<impl AsRef<T>>)
     check_button
 }
 
+pub fn append_entity<T: ToVariant>(first_check_button: &CheckButton,
+    entity: &(impl IsA<Widget>, Option<impl AsRef<T>>)) -> CheckButton {
+    let check_button = check_button(entity, &first_check_button.parent().and_downcast::<gtk::Box>().unwrap());
+    check_button.set_group(Some(first_check_button));
+    check_button
+}
+
 pub fn check_button_dialog<T: ToVariant + StaticVariantType, F: FnMut(&Overlay, Variant, &Window) + 'static>(
     heading: &str, description: Option<&str>, entities: &Vec<(impl IsA<Widget>, Option<impl AsRef<T>>)>,
     choose_button_label: &str, default: T, css_class: &'static str, on_click: RefCell<F>) -> Vec<CheckButton> {
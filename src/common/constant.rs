@@ -3,3 +3,4 @@ pub const INSENSITIVE_FG: &str = "insensitive-fg";
 pub const DESTRUCTIVE_ACTION: &str = "destructive-action";
 pub const SUGGESTED_ACTION: &str = "suggested-action";
 pub const NONE: &str = "None";
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
@@ -2,7 +2,8 @@ use diesel::prelude::*;
 use diesel::update;
 use crate::db::get_connection;
 use crate::schema::config::dsl::config;
-use crate::schema::config::now_playing_body_realized;
+use crate::schema::config::{crossfade, loudness_analysis, now_playing_body_realized, repeat_mode, various_artists,
+    write_replay_gain};
 
 #[derive(Queryable, Selectable, Debug)]
 #[diesel(table_name = crate::schema::config)]
@@ -14,9 +15,52 @@ pub struct Config {
     pub window_height: i32,
     pub maximized: i32,
     pub now_playing_body_realized: i32,
+    pub current_queue_id: Option<i32>,
+    pub repeat_mode: RepeatMode,
+    pub shuffle_seed: Option<i64>,
+    pub shuffle_start: Option<i32>,
+    pub various_artists: VariousArtists,
+    pub loudness_analysis: i32,
+    pub write_replay_gain: i32,
+    pub crossfade: i32,
+    pub volume: f64,
+    pub mute: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, diesel_derive_enum::DbEnum)]
+pub enum RepeatMode {
+    Off,
+    One,
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, diesel_derive_enum::DbEnum)]
+pub enum VariousArtists {
+    Grouped,
+    TrackArtists,
 }
 
 pub fn update_now_playing_body_realized(realized: bool) {
     update(config).set(now_playing_body_realized.eq(if realized { 1 } else { 0 })).execute(&mut get_connection())
         .unwrap();
 }
+
+pub fn update_repeat_mode(mode: RepeatMode) {
+    update(config).set(repeat_mode.eq(mode)).execute(&mut get_connection()).unwrap();
+}
+
+pub fn update_various_artists(mode: VariousArtists) {
+    update(config).set(various_artists.eq(mode)).execute(&mut get_connection()).unwrap();
+}
+
+pub fn update_loudness_analysis(running: bool) {
+    update(config).set(loudness_analysis.eq(if running { 1 } else { 0 })).execute(&mut get_connection()).unwrap();
+}
+
+pub fn update_write_replay_gain(write: bool) {
+    update(config).set(write_replay_gain.eq(if write { 1 } else { 0 })).execute(&mut get_connection()).unwrap();
+}
+
+pub fn update_crossfade(seconds: i32) {
+    update(config).set(crossfade.eq(seconds)).execute(&mut get_connection()).unwrap();
+}
@@ -18,18 +18,23 @@ use log::info;
 use db::MIGRATIONS;
 use crate::body::{BodyTable, BodyType, BODY_TYPE, PARAMS};
 use crate::body::artists::artists_page;
+use crate::body::collection::watch_collections;
 use crate::body::collection::page::{COLLECTION, collection_page};
 use crate::body::download::albums::albums_page;
 use crate::body::download::songs::songs_page;
+use crate::body::facet::{Facet, facet_page};
+use crate::body::merge::history::merge_history_page;
+use crate::body::search::search_page;
+use crate::body::tag_editor::tag_editor_page;
 use crate::common::constant::APP_ID;
 use crate::common::state::State;
 use crate::common::window_action::WindowActions;
 use crate::config::Config;
 use crate::db::get_connection;
-use crate::now_playing::playbin::{PLAYBIN, Playbin};
+use crate::now_playing::playbin::{MUTE, PLAYBIN, Playbin, VOLUME};
 use crate::schema::bodies::{body_type, params, scroll_adjustment};
 use crate::schema::bodies::dsl::bodies;
-use crate::schema::config::{current_song_position, maximized, window_height, window_width};
+use crate::schema::config::{current_song_position, maximized, mute, volume, window_height, window_width};
 use crate::schema::config::dsl::config as config_table;
 
 mod schema;
@@ -39,6 +44,8 @@ mod common;
 mod config;
 mod body;
 mod song;
+mod queue;
+mod tag;
 
 fn handle_scroll(scroll: Option<f64>, navigation_page: &NavigationPage) {
     let signal_handler_id = Rc::new(RefCell::new(None::<SignalHandlerId>));
@@ -97,6 +104,7 @@ fn main() -> Result<ExitCode> {
         state.navigation_view.add(&artists_page);
         let collection_page = collection_page(state.clone());
         state.navigation_view.add(&collection_page);
+        watch_collections(state.clone());
         for body_table in history_bodies {
             let body_params = serde_json::from_str::<Vec<Option<String>>>(&body_table.params).unwrap().into_iter()
                 .map(|it| { it.map(Arc::new) }).collect();
@@ -105,6 +113,15 @@ fn main() -> Result<ExitCode> {
                 BodyType::Artists => { handle_scroll(scroll, &artists_page); }
                 BodyType::Albums => { state.navigation_view.push(&albums_page(body_params, state.clone(), scroll)); }
                 BodyType::Songs => { state.navigation_view.push(&songs_page(body_params, state.clone(), scroll)); }
+                BodyType::Search => { state.navigation_view.push(&search_page(body_params, state.clone(), scroll)); }
+                BodyType::Genres => { state.navigation_view.push(&facet_page(Facet::Genre, state.clone(), scroll)); }
+                BodyType::Years => { state.navigation_view.push(&facet_page(Facet::Year, state.clone(), scroll)); }
+                BodyType::MergeHistory => {
+                    state.navigation_view.push(&merge_history_page(state.clone(), scroll));
+                }
+                BodyType::TagEditor => {
+                    state.navigation_view.push(&tag_editor_page(body_params, state.clone(), scroll));
+                }
                 BodyType::Collections => {
                     state.navigation_view.push_by_tag(COLLECTION);
                     handle_scroll(scroll, &collection_page);
@@ -120,7 +137,9 @@ fn main() -> Result<ExitCode> {
                 let (width, height) = window.default_size();
                 update(config_table).set((window_width.eq(width), window_height.eq(height),
                     maximized.eq(if window.is_maximized() { 1 } else { 0 }),
-                    current_song_position.eq(PLAYBIN.get_position().unwrap_or(0) as i64)
+                    current_song_position.eq(PLAYBIN.get_position().unwrap_or(0) as i64),
+                    volume.eq(PLAYBIN.property::<f64>(VOLUME)),
+                    mute.eq(if PLAYBIN.property::<bool>(MUTE) { 1 } else { 0 }),
                 )).execute(&mut get_connection()).unwrap();
                 delete(bodies).execute(&mut get_connection()).unwrap();
                 insert_into(bodies).values(
@@ -0,0 +1,227 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use adw::prelude::*;
+use diesel::{delete, ExpressionMethods, insert_into, QueryDsl, RunQueryDsl};
+use gstreamer::SeekFlags;
+use gtk::{Button, Entry, Label, MenuButton, Popover, PositionType, Scale};
+use gtk::Orientation::Vertical;
+use log::warn;
+use crate::common::{StyledLabelBuilder, StyledWidget};
+use crate::common::constant::INSENSITIVE_FG;
+use crate::common::util::format;
+use crate::db::get_connection;
+use crate::now_playing::playbin::{PLAYBIN, Playbin};
+use crate::schema::loops;
+
+#[derive(diesel::Queryable, diesel::Selectable, Debug)]
+#[diesel(table_name = crate::schema::loops)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Loop {
+    pub id: i32,
+    pub song_id: i32,
+    pub name: String,
+    pub start_position: i64,
+    pub end_position: i64,
+}
+
+fn format_range(start: u64, end: u64) -> String {
+    format!("{} – {}", format(start), format(end))
+}
+
+// played as a segment so that PLAYBIN posts SegmentDone at B, which sends it back to A
+pub struct AbLoop {
+    pub loop_box: gtk::Box,
+    scale: Scale,
+    rate: Scale,
+    song_id: Cell<Option<i32>>,
+    a: Cell<Option<u64>>,
+    b: Cell<Option<u64>>,
+    range_label: Label,
+    b_button: Button,
+    clear_button: Button,
+    save_button: Button,
+}
+
+impl AbLoop {
+    pub(super) fn new(scale: &Scale, rate: &Scale) -> Rc<Self> {
+        let loop_box = gtk::Box::builder().spacing(4).build();
+        let a_button = Button::builder().label("A").tooltip_text("Loop from here").build().flat();
+        loop_box.append(&a_button);
+        let b_button = Button::builder().label("B").tooltip_text("Loop up to here").sensitive(false).build().flat();
+        loop_box.append(&b_button);
+        let range_label = Label::builder().hexpand(true).subscript().name(INSENSITIVE_FG).build().numeric();
+        loop_box.append(&range_label);
+        let clear_button = Button::builder().icon_name("edit-clear-symbolic").tooltip_text("Stop looping")
+            .sensitive(false).build().flat();
+        loop_box.append(&clear_button);
+        let saved_box = gtk::Box::builder().orientation(Vertical).spacing(4).build();
+        let popover_box = gtk::Box::builder().orientation(Vertical).spacing(8).build();
+        popover_box.append(&saved_box);
+        let save_box = gtk::Box::builder().spacing(4).build();
+        popover_box.append(&save_box);
+        let name_entry = Entry::builder().placeholder_text("Loop name").hexpand(true).build();
+        save_box.append(&name_entry);
+        let save_button = Button::builder().label("Save").sensitive(false).build();
+        save_box.append(&save_button);
+        let popover = Popover::builder().child(&popover_box).build();
+        loop_box.append(&MenuButton::builder().icon_name("user-bookmarks-symbolic").tooltip_text("Saved loops")
+            .popover(&popover).build());
+        let this = Rc::new(Self {
+            loop_box,
+            scale: scale.clone(),
+            rate: rate.clone(),
+            song_id: Cell::new(None),
+            a: Cell::new(None),
+            b: Cell::new(None),
+            range_label,
+            b_button,
+            clear_button,
+            save_button,
+        });
+        a_button.connect_clicked({
+            let this = this.clone();
+            move |_| {
+                if let Some(position) = PLAYBIN.get_position() {
+                    if this.range().is_some() { this.release(); }
+                    this.a.set(Some(position));
+                    this.update();
+                }
+            }
+        });
+        this.b_button.connect_clicked({
+            let this = this.clone();
+            move |_| {
+                if let (Some(a), Some(position)) = (this.a.get(), PLAYBIN.get_position()) {
+                    if position != a { this.start(a.min(position), a.max(position)); }
+                }
+            }
+        });
+        this.clear_button.connect_clicked({
+            let this = this.clone();
+            move |_| { this.release(); }
+        });
+        this.save_button.connect_clicked({
+            let this = this.clone();
+            let popover = popover.clone();
+            move |_| {
+                if let (Some(song_id), Some((start, end))) = (this.song_id.get(), this.range()) {
+                    let text = name_entry.text();
+                    let name = if text.trim().is_empty() { format_range(start, end) } else { text.trim().to_owned() };
+                    insert_into(loops::table).values((loops::song_id.eq(song_id), loops::name.eq(name),
+                        loops::start_position.eq(start as i64), loops::end_position.eq(end as i64)))
+                        .execute(&mut get_connection()).unwrap();
+                    name_entry.set_text("");
+                    popover.popdown();
+                }
+            }
+        });
+        popover.connect_show({
+            let this = this.clone();
+            move |popover| { this.show_saved(&saved_box, popover); }
+        });
+        this
+    }
+    fn show_saved(self: &Rc<Self>, saved_box: &gtk::Box, popover: &Popover) {
+        while let Some(child) = saved_box.first_child() {
+            saved_box.remove(&child);
+        }
+        let saved = self.song_id.get().map(|song_id| {
+            loops::table.filter(loops::song_id.eq(song_id)).order_by(loops::start_position)
+                .get_results::<Loop>(&mut get_connection()).unwrap()
+        }).unwrap_or_default();
+        if saved.is_empty() {
+            saved_box.append(&Label::builder().label("No saved loops").name(INSENSITIVE_FG).build());
+        }
+        for saved_loop in saved {
+            let (start, end) = (saved_loop.start_position as u64, saved_loop.end_position as u64);
+            let row = gtk::Box::builder().spacing(4).build();
+            saved_box.append(&row);
+            let label_box = gtk::Box::builder().orientation(Vertical).build();
+            label_box.append(&Label::builder().label(&saved_loop.name).xalign(0.0).ellipsized().build());
+            label_box.append(&Label::builder().label(&format_range(start, end)).xalign(0.0).subscript()
+                .name(INSENSITIVE_FG).build().numeric());
+            let recall_button = Button::builder().child(&label_box).hexpand(true).build().flat();
+            row.append(&recall_button);
+            recall_button.connect_clicked({
+                let this = self.clone();
+                let popover = popover.clone();
+                move |_| {
+                    this.start(start, end);
+                    popover.popdown();
+                }
+            });
+            let delete_button = Button::builder().icon_name("user-trash-symbolic").tooltip_text("Delete loop").build()
+                .flat();
+            row.append(&delete_button);
+            delete_button.connect_clicked({
+                let this = self.clone();
+                let saved_box = saved_box.clone();
+                let popover = popover.clone();
+                move |_| {
+                    delete(loops::table.find(saved_loop.id)).execute(&mut get_connection()).unwrap();
+                    this.show_saved(&saved_box, &popover);
+                }
+            });
+        }
+    }
+    fn update(&self) {
+        self.scale.clear_marks();
+        if let Some(a) = self.a.get() { self.scale.add_mark(a as f64, PositionType::Top, Some("A")); }
+        if let Some(b) = self.b.get() { self.scale.add_mark(b as f64, PositionType::Top, Some("B")); }
+        let range = self.range();
+        self.range_label.set_label(&match (self.a.get(), range) {
+            (_, Some((start, end))) => { format_range(start, end) }
+            (Some(a), None) => { format!("{} –", format(a)) }
+            (None, None) => { String::new() }
+        });
+        self.b_button.set_sensitive(self.a.get().is_some());
+        self.clear_button.set_sensitive(self.a.get().is_some());
+        self.save_button.set_sensitive(range.is_some());
+    }
+    pub(super) fn range(&self) -> Option<(u64, u64)> {
+        self.a.get().zip(self.b.get())
+    }
+    fn seek(&self, flags: SeekFlags, position: u64, stop: Option<u64>) {
+        if let Err(error) = PLAYBIN.seek_range(self.rate.value(), flags, position, stop) {
+            warn!("error seeking to [{position}] with loop end [{stop:?}] [{error}]");
+        }
+    }
+    fn start(&self, start: u64, end: u64) {
+        self.a.set(Some(start));
+        self.b.set(Some(end));
+        self.update();
+        self.seek(SeekFlags::FLUSH | SeekFlags::ACCURATE, start, Some(end));
+    }
+    fn release(&self) {
+        let looping = self.range().is_some();
+        self.clear();
+        if let Some(position) = PLAYBIN.get_position().filter(|_| { looping }) {
+            self.seek(SeekFlags::FLUSH | SeekFlags::ACCURATE, position, None);
+        }
+    }
+    pub(super) fn clear(&self) {
+        self.a.set(None);
+        self.b.set(None);
+        self.update();
+    }
+    pub(super) fn set_song(&self, song_id: i32) {
+        self.song_id.set(Some(song_id));
+        self.clear();
+    }
+    pub(super) fn stop_at(&self, position: u64) -> Option<u64> {
+        match self.range() {
+            Some((start, end)) if (start..end).contains(&position) => { Some(end) }
+            Some(_) => {
+                self.clear();
+                None
+            }
+            None => { None }
+        }
+    }
+    // no flush so that the loop plays on seamlessly
+    pub(super) fn repeat(&self) {
+        if let Some((start, end)) = self.range() {
+            self.seek(SeekFlags::ACCURATE, start, Some(end));
+        }
+    }
+}
@@ -2,20 +2,50 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use adw::HeaderBar;
 use adw::prelude::*;
-use gtk::{Button, GestureSwipe, Image};
+use gtk::{Button, GestureSwipe, Image, Label, MenuButton, Popover, ScrolledWindow, ToggleButton};
 use gtk::Orientation::Vertical;
 use crate::common::StyledWidget;
+use crate::now_playing::crossfade::crossfade_box;
 use crate::now_playing::now_playing::NowPlaying;
 use crate::now_playing::playbin::{PLAYBIN, Playbin};
 
-pub(super) fn create(now_playing: Rc<RefCell<NowPlaying>>) -> (gtk::Box, Button, GestureSwipe) {
+pub(super) fn create(now_playing: Rc<RefCell<NowPlaying>>, queue_window: &ScrolledWindow)
+    -> (gtk::Box, Button, GestureSwipe) {
     let body = gtk::Box::builder().orientation(Vertical).margin_bottom(48).build();
     let header_bar = HeaderBar::builder().title_widget(&now_playing.borrow().window_title).build();
     body.append(&header_bar);
     let down_button = Button::builder().icon_name("go-down").build();
     header_bar.pack_start(&down_button);
+    let queue_button = ToggleButton::builder().icon_name("view-list-symbolic").tooltip_text("Queue").build();
+    header_bar.pack_end(&queue_button);
+    let lyrics_button = ToggleButton::builder().icon_name("format-justify-center-symbolic").tooltip_text("Lyrics")
+        .build();
+    header_bar.pack_end(&lyrics_button);
+    let playback_box = gtk::Box::builder().orientation(Vertical).spacing(8).build();
+    playback_box.append(&crossfade_box());
+    let rate_box = gtk::Box::builder().spacing(8).margin_start(4).margin_end(4).build();
+    playback_box.append(&rate_box);
+    rate_box.append(&Label::builder().label("Speed").xalign(0.0).build());
+    rate_box.append(&now_playing.borrow().rate);
+    header_bar.pack_start(&MenuButton::builder().icon_name("emblem-system-symbolic").tooltip_text("Playback")
+        .popover(&Popover::builder().child(&playback_box).build()).build());
     let image_and_song_info = gtk::Box::builder().orientation(Vertical).build();
     body.append(&image_and_song_info);
+    body.append(queue_window);
+    let lyrics_window = now_playing.borrow().lyrics.scrolled_window.clone();
+    body.append(&lyrics_window);
+    for (toggle_button, other_button, window) in [(&queue_button, &lyrics_button, queue_window.clone()),
+        (&lyrics_button, &queue_button, lyrics_window)] {
+        toggle_button.connect_toggled({
+            let image_and_song_info = image_and_song_info.clone();
+            let other_button = other_button.clone();
+            move |toggle_button| {
+                if toggle_button.is_active() { other_button.set_active(false); }
+                image_and_song_info.set_visible(!toggle_button.is_active() && !other_button.is_active());
+                window.set_visible(toggle_button.is_active());
+            }
+        });
+    }
     image_and_song_info.append(&now_playing.borrow().body_image);
     let song_info = gtk::Box::builder().orientation(Vertical).spacing(4).margin_start(8).margin_end(8).margin_bottom(4)
         .build();
@@ -31,6 +61,7 @@ pub(super) fn create(now_playing: Rc<RefCell<NowPlaying>>) -> (gtk::Box, Button,
     time_and_controls.append(&time);
     time.append(&now_playing.borrow().body_position);
     time.append(&now_playing.borrow().body_duration);
+    time_and_controls.append(&now_playing.borrow().ab_loop.loop_box);
     let controls = gtk::Box::builder().build();
     time_and_controls.append(&controls);
     let skip_backward = Button::builder().hexpand(true).tooltip_text("Previous")
@@ -39,8 +70,14 @@ pub(super) fn create(now_playing: Rc<RefCell<NowPlaying>>) -> (gtk::Box, Button,
     let skip_forward = Button::builder().hexpand(true).tooltip_text("Next")
         .child(&Image::builder().icon_name("media-skip-forward").pixel_size(28).build()).build().flat();
     skip_forward.connect_clicked(|_| { PLAYBIN.go_delta_song(1, true); });
+    controls.append(&now_playing.borrow().shuffle);
     controls.append(&skip_backward);
     controls.append(&now_playing.borrow().body_play_pause);
     controls.append(&skip_forward);
+    controls.append(&now_playing.borrow().repeat);
+    let volume = gtk::Box::builder().margin_start(8).margin_end(8).build();
+    time_and_controls.append(&volume);
+    volume.append(&now_playing.borrow().mute);
+    volume.append(&now_playing.borrow().volume);
     (body, down_button, skip_song_gesture)
 }
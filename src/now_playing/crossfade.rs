@@ -0,0 +1,195 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use adw::prelude::*;
+use diesel::{RunQueryDsl, SqliteConnection};
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use gstreamer::{ClockTime, Pipeline, SeekFlags, SeekType};
+use gstreamer::glib::{ControlFlow::*, timeout_add_local};
+use gstreamer::prelude::{ElementExt, ElementExtManual, ObjectExt as GstreamerObject};
+use gstreamer::State::{Null, Paused, Playing, VoidPending};
+use gtk::{Label, SpinButton};
+use log::warn;
+use once_cell::sync::Lazy;
+use crate::config::{Config, update_crossfade};
+use crate::db::get_connection;
+use crate::now_playing::playbin::{MUTE, playbin, PLAYBIN, Playbin, URI, VOLUME};
+use crate::queue::delta_song;
+use crate::schema::config::dsl::config;
+use crate::song::{get_current_song, Song};
+
+// plays the tail of the song fading out while PLAYBIN plays the next one
+static FADER: Lazy<Pipeline> = Lazy::new(|| {
+    let fader = playbin();
+    fader.bus().unwrap().set_flushing(true);
+    fader
+});
+static CROSSFADE_NEXT: AtomicBool = AtomicBool::new(false);
+static CROSSFADE: AtomicU64 = AtomicU64::new(0);
+// whichever of the crossfade and about-to-finish claims the end of the song first moves on to the next one
+static CLAIMED: AtomicBool = AtomicBool::new(false);
+const CURRENT_URI: &'static str = "current-uri";
+const PREPARE: Duration = Duration::from_secs(1);
+const LEAD: Duration = Duration::from_millis(200);
+const STEP: Duration = Duration::from_millis(20);
+
+fn follow_volume() {
+    FADER.set_property(VOLUME, PLAYBIN.property::<f64>(VOLUME));
+    FADER.set_property(MUTE, PLAYBIN.property::<bool>(MUTE));
+}
+
+fn crossfade_next() -> bool {
+    CROSSFADE_NEXT.load(Ordering::Relaxed)
+}
+
+fn claim() -> bool {
+    CLAIMED.compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+}
+
+pub(super) fn ends_in_crossfade() -> bool {
+    if !crossfade_next() { return false; }
+    let start = PLAYBIN.query_duration::<ClockTime>().map(ClockTime::nseconds).unwrap_or(0)
+        .saturating_sub(CROSSFADE.load(Ordering::Relaxed));
+    let missed = PLAYBIN.get_position().map(|position| { position + PREPARE.as_nanos() as u64 >= start })
+        .unwrap_or(true);
+    !(missed && claim())
+}
+
+fn consecutive(song: &Song, next: &Song) -> bool {
+    song.album_id == next.album_id && match (song.track_number, next.track_number) {
+        (Some(track), Some(next_track)) => {
+            let (volume, next_volume) = (song.album_volume.unwrap_or(1), next.album_volume.unwrap_or(1));
+            (next_volume == volume && next_track == track + 1) || (next_volume == volume + 1 && next_track == 1)
+        }
+        _ => { true }
+    }
+}
+
+fn fades_into_next(crossfade: Duration, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> anyhow::Result<bool> {
+    let (song, _, _) = get_current_song(connection)?;
+    Ok(!crossfade.is_zero() && song.duration as u128 > 2 * crossfade.as_nanos()
+        && delta_song(1, true, connection)?.map(|(next, _, _)| { next.id != song.id && !consecutive(&song, &next) })
+        .unwrap_or(false))
+}
+
+pub(super) fn decide() {
+    let connection = &mut get_connection();
+    let Config { crossfade, .. } = config.get_result::<Config>(connection).unwrap();
+    let crossfade = Duration::from_secs(crossfade as u64);
+    CROSSFADE.store(crossfade.as_nanos() as u64, Ordering::Relaxed);
+    CROSSFADE_NEXT.store(fades_into_next(crossfade, connection).unwrap_or(false), Ordering::Relaxed);
+    CLAIMED.store(false, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Idle,
+    Preparing,
+    Fading(Instant),
+}
+
+pub struct Crossfader {
+    phase: Cell<Phase>,
+}
+
+impl Crossfader {
+    pub(super) fn new() -> Rc<Self> {
+        Rc::new(Self { phase: Cell::new(Phase::Idle) })
+    }
+    fn stop(&self) {
+        FADER.set_state(Null).unwrap();
+        PLAYBIN.set_fade(1.0);
+        self.phase.set(Phase::Idle);
+        CLAIMED.store(false, Ordering::Relaxed);
+    }
+    pub(super) fn on_position(self: &Rc<Self>, position: u64, duration: u64, rate: f64) {
+        let crossfade = CROSSFADE.load(Ordering::Relaxed);
+        let start = duration.saturating_sub(crossfade);
+        if self.phase.get() == Phase::Idle && crossfade_next() && position + PREPARE.as_nanos() as u64 >= start
+            && claim() {
+            self.prepare(start, Duration::from_nanos(crossfade), rate);
+        }
+    }
+    fn prepare(self: &Rc<Self>, start: u64, crossfade: Duration, rate: f64) {
+        let uri = PLAYBIN.property::<String>(CURRENT_URI);
+        FADER.set_property(URI, &uri);
+        if let Ok((song, Config { current_queue_id, shuffle_seed, .. }, _)) = get_current_song(&mut get_connection()) {
+            FADER.set_replay_gain(&song, current_queue_id.is_none() && shuffle_seed.is_none());
+        }
+        FADER.set_fade(1.0);
+        follow_volume();
+        if let Err(error) = FADER.set_state(Paused) {
+            warn!("error prerolling [{uri}] to crossfade [{error}]");
+            CROSSFADE_NEXT.store(false, Ordering::Relaxed);
+            return;
+        }
+        self.phase.set(Phase::Preparing);
+        let target = Cell::new(None::<u64>);
+        timeout_add_local(STEP, {
+            let this = self.clone();
+            move || {
+                let stopped = PLAYBIN.current_state() != Playing && PLAYBIN.pending_state() != Playing;
+                match this.phase.get() {
+                    Phase::Idle => { return Break; }
+                    Phase::Preparing => {
+                        if stopped || PLAYBIN.property::<String>(CURRENT_URI) != uri {
+                            this.stop();
+                            return Break;
+                        }
+                        if FADER.current_state() != Paused || FADER.pending_state() != VoidPending { return Continue; }
+                        let position = PLAYBIN.get_position().unwrap_or(0);
+                        match target.get() {
+                            None => {
+                                let seek_target = start.max(position + LEAD.as_nanos() as u64);
+                                if let Err(error) = FADER.seek(rate, SeekFlags::FLUSH | SeekFlags::ACCURATE,
+                                    SeekType::Set, ClockTime::from_nseconds(seek_target), SeekType::None,
+                                    ClockTime::NONE) {
+                                    warn!("error seeking [{uri}] to crossfade [{error}]");
+                                    CROSSFADE_NEXT.store(false, Ordering::Relaxed);
+                                    this.stop();
+                                    return Break;
+                                }
+                                target.set(Some(seek_target));
+                            }
+                            Some(seek_target) if position >= seek_target => {
+                                FADER.set_state(Playing).unwrap();
+                                PLAYBIN.set_fade(0.0);
+                                PLAYBIN.go_delta_song(1, true);
+                                this.phase.set(Phase::Fading(Instant::now()));
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                    Phase::Fading(started) => {
+                        let progress = started.elapsed().as_secs_f64() / crossfade.as_secs_f64();
+                        if stopped || progress >= 1.0 {
+                            this.stop();
+                            return Break;
+                        }
+                        follow_volume();
+                        FADER.set_fade((1.0 - progress).sqrt());
+                        PLAYBIN.set_fade(progress.sqrt());
+                    }
+                }
+                Continue
+            }
+        });
+    }
+}
+
+pub(super) fn crossfade_box() -> gtk::Box {
+    let crossfade_box = gtk::Box::builder().spacing(8).margin_start(4).margin_end(4).build();
+    crossfade_box.append(&Label::builder().label("Crossfade seconds").hexpand(true).xalign(0.0).build());
+    let Config { crossfade, .. } = config.get_result::<Config>(&mut get_connection()).unwrap();
+    let spin_button = SpinButton::with_range(0.0, 12.0, 1.0);
+    spin_button.set_value(crossfade as f64);
+    spin_button.set_tooltip_text(Some("Consecutive songs of an album always stay gapless, 0 turns crossfading off"));
+    crossfade_box.append(&spin_button);
+    spin_button.connect_value_changed(|spin_button| {
+        update_crossfade(spin_button.value_as_int());
+        decide();
+    });
+    crossfade_box
+}
@@ -0,0 +1,194 @@
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, TryRecvError::*};
+use std::time::Duration;
+use adw::prelude::*;
+use gstreamer::glib::{ControlFlow::*, timeout_add_local};
+use gtk::{Button, Justification, Label, ScrolledWindow};
+use gtk::Align::Center;
+use gtk::Orientation::Vertical;
+use log::{error, warn};
+use once_cell::sync::Lazy;
+use lrclib::Lrclib;
+use metadata_fetch::LyricsFetcher;
+use crate::common::StyledWidget;
+use crate::common::constant::INSENSITIVE_FG;
+use crate::common::util::or_none;
+use crate::song::Song;
+
+static LRCLIB: Lazy<Lrclib> = Lazy::new(|| { Lrclib::default() });
+
+enum Lyrics {
+    Plain(String),
+    // start positions in nanoseconds
+    Synced(Vec<(u64, String)>),
+}
+
+fn timestamp(tag: &str) -> Option<u64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes = minutes.trim().parse::<u64>().ok()?;
+    let seconds = seconds.trim().replacen(':', ".", 1).parse::<f64>().ok()?;
+    Some(Duration::from_secs(minutes * 60).as_nanos() as u64 + Duration::from_secs_f64(seconds).as_nanos() as u64)
+}
+
+fn parse(text: &str) -> Lyrics {
+    let mut offset_millis = 0i64;
+    let mut synced = Vec::new();
+    let mut plain = Vec::new();
+    for line in text.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        let mut tagged = false;
+        while let Some((tag, after)) = rest.strip_prefix('[').and_then(|it| { it.split_once(']') }) {
+            tagged = true;
+            if let Some(time) = timestamp(tag) {
+                times.push(time);
+            } else if let Some(offset) = tag.strip_prefix("offset:") {
+                offset_millis = offset.trim().parse().unwrap_or(0);
+            }
+            rest = after;
+        }
+        if !times.is_empty() {
+            synced.extend(times.into_iter().map(|time| { (time, rest.trim().to_owned()) }));
+        } else if !tagged {
+            plain.push(line);
+        }
+    }
+    if synced.is_empty() {
+        Lyrics::Plain(plain.join("\n").trim().to_owned())
+    } else {
+        let offset = Duration::from_millis(offset_millis.unsigned_abs()).as_nanos() as u64;
+        for (time, _) in synced.iter_mut() {
+            *time = if offset_millis > 0 { time.saturating_sub(offset) } else { *time + offset };
+        }
+        synced.sort_by_key(|(time, _)| { *time });
+        Lyrics::Synced(synced)
+    }
+}
+
+fn sidecar(song_path: &PathBuf) -> PathBuf {
+    song_path.with_extension("lrc")
+}
+
+fn read(song_path: &PathBuf, tag_lyrics: &Option<String>) -> Option<Lyrics> {
+    fs::read_to_string(sidecar(song_path)).ok().or(tag_lyrics.clone()).filter(|it| { !it.trim().is_empty() })
+        .map(|it| { parse(&it) })
+}
+
+pub struct LyricsView {
+    pub scrolled_window: ScrolledWindow,
+    song_id: Cell<Option<i32>>,
+    lines: RefCell<Vec<(u64, Label)>>,
+    current_line: Cell<Option<usize>>,
+}
+
+impl LyricsView {
+    pub(super) fn new() -> Rc<Self> {
+        Rc::new(Self {
+            scrolled_window: ScrolledWindow::builder().vexpand(true).visible(false).build(),
+            song_id: Cell::new(None),
+            lines: RefCell::new(Vec::new()),
+            current_line: Cell::new(None),
+        })
+    }
+    fn lyrics_box() -> gtk::Box {
+        gtk::Box::builder().orientation(Vertical).spacing(8).margin_start(12).margin_end(12).margin_top(12)
+            .margin_bottom(12).build()
+    }
+    fn line(text: &str) -> Label {
+        Label::builder().label(text).wrap(true).justify(Justification::Center).build()
+    }
+    fn render(&self, lyrics_box: gtk::Box, lines: Vec<(u64, Label)>) {
+        *self.lines.borrow_mut() = lines;
+        self.current_line.set(None);
+        self.scrolled_window.set_child(Some(&lyrics_box));
+    }
+    fn set_lyrics(&self, lyrics: Lyrics) {
+        let lyrics_box = Self::lyrics_box();
+        let mut lines = Vec::new();
+        match lyrics {
+            Lyrics::Plain(text) => { lyrics_box.append(&Self::line(&text)); }
+            Lyrics::Synced(synced) => {
+                for (time, text) in synced {
+                    let label = Self::line(&text);
+                    label.set_name(INSENSITIVE_FG);
+                    lyrics_box.append(&label);
+                    lines.push((time, label));
+                }
+            }
+        }
+        self.render(lyrics_box, lines);
+    }
+    fn set_missing(self: &Rc<Self>, song: &Song, song_path: &PathBuf) {
+        let lyrics_box = Self::lyrics_box();
+        lyrics_box.append(&Label::builder().label("No lyrics").name(INSENSITIVE_FG).build());
+        let search_button = Button::builder().label("Search online").halign(Center).build();
+        lyrics_box.append(&search_button);
+        search_button.connect_clicked({
+            let this = self.clone();
+            let song_path = song_path.clone();
+            let (artist, album, title) = (or_none(&song.artist).to_owned(), or_none(&song.album).to_owned(),
+                song.title_str().to_owned());
+            let duration = Duration::from_nanos(song.duration as u64);
+            let song_id = song.id;
+            move |search_button| {
+                search_button.set_sensitive(false);
+                let (sender, receiver) = channel();
+                LRCLIB.download_lyrics(&artist, &album, &title, duration, sender);
+                timeout_add_local(Duration::from_millis(500), {
+                    let this = this.clone();
+                    let song_path = song_path.clone();
+                    let search_button = search_button.clone();
+                    move || {
+                        match receiver.try_recv() {
+                            Err(Empty) => { return Continue; }
+                            Err(Disconnected) => {}
+                            Ok(Ok(Some(text))) => {
+                                if let Err(error) = fs::write(sidecar(&song_path), &text) {
+                                    error!("error saving lyrics of song [{song_id}] [{error}]");
+                                }
+                                if this.song_id.get() == Some(song_id) { this.set_lyrics(parse(&text)); }
+                            }
+                            Ok(Ok(None)) => { search_button.set_label("No lyrics found"); }
+                            Ok(Err(error)) => {
+                                warn!("error downloading lyrics of song [{song_id}] [{error}]");
+                                search_button.set_label("Search failed, try again");
+                                search_button.set_sensitive(true);
+                            }
+                        }
+                        Break
+                    }
+                });
+            }
+        });
+        self.render(lyrics_box, Vec::new());
+    }
+    pub(super) fn show(self: &Rc<Self>, song: &Song, song_path: &PathBuf) {
+        self.song_id.set(Some(song.id));
+        match read(song_path, &song.lyrics) {
+            Some(lyrics) => { self.set_lyrics(lyrics); }
+            None => { self.set_missing(song, song_path); }
+        }
+    }
+    pub(super) fn set_position(&self, position: u64) {
+        let lines = self.lines.borrow();
+        let line = lines.partition_point(|(time, _)| { *time <= position }).checked_sub(1);
+        if line == self.current_line.get() { return; }
+        if let Some(previous) = self.current_line.get() {
+            let (_, label) = &lines[previous];
+            label.remove_css_class("accent");
+            label.set_name(INSENSITIVE_FG);
+        }
+        if let Some(line) = line {
+            let (_, label) = &lines[line];
+            label.set_name(None::<String>);
+            label.add_css_class("accent");
+            let adjustment = self.scrolled_window.vadjustment();
+            let allocation = label.allocation();
+            adjustment.set_value((allocation.y() + allocation.height() / 2) as f64 - adjustment.page_size() / 2.0);
+        }
+        self.current_line.set(line);
+    }
+}
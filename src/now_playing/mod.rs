@@ -7,7 +7,7 @@ use adw::glib::Propagation;
 use adw::prelude::*;
 use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, TextExpressionMethods, update};
 use gstreamer::glib::{ControlFlow::*, timeout_add_local};
-use gstreamer::MessageView::{AsyncDone, DurationChanged, StateChanged, StreamStart};
+use gstreamer::MessageView::{AsyncDone, DurationChanged, SegmentDone, StateChanged, StreamStart};
 use gstreamer::prelude::{ElementExt, ElementExtManual, GstObjectExt, ObjectExt as GstreamerObject};
 use gstreamer::State::{Null, Paused, Playing};
 use gtk::{EventSequenceState, ScrollType};
@@ -17,11 +17,14 @@ use crate::body::collection::model::Collection;
 use crate::common::gesture::{Direction, DirectionSwipe};
 use crate::common::state::State;
 use crate::common::util::or_none;
-use crate::config::{Config, update_now_playing_body_realized};
+use crate::config::{Config, RepeatMode, update_now_playing_body_realized, update_repeat_mode};
 use crate::db::get_connection;
-use crate::now_playing::mpris::mpris_player;
+use crate::now_playing::crossfade::decide;
+use crate::now_playing::mpris::{loop_status, MAXIMUM_RATE, MINIMUM_RATE, mpris_player, repeat_mode};
 use crate::now_playing::now_playing::{NowPlaying, Playable};
-use crate::now_playing::playbin::{PLAYBIN, Playbin, URI};
+use crate::now_playing::playbin::{MUTE, PLAYBIN, Playbin, URI, VOLUME};
+use crate::now_playing::queue::QueueView;
+use crate::queue::set_shuffle;
 use crate::schema::collections::dsl::collections;
 use crate::schema::collections::path;
 use crate::schema::config::current_song_id;
@@ -36,6 +39,10 @@ mod mpris;
 mod now_playing;
 mod bottom_widget;
 mod body;
+mod queue;
+mod lyrics;
+mod crossfade;
+mod ab_loop;
 
 fn go_delta_song(velocity_x: f64) {
     PLAYBIN.go_delta_song(if velocity_x > 0.0 { -1 } else { 1 }, true);
@@ -43,7 +50,9 @@ fn go_delta_song(velocity_x: f64) {
 
 pub fn create(state: Rc<State>) -> (gtk::Box, gtk::Box, Rc<RefCell<NowPlaying>>) {
     let now_playing = Rc::new(RefCell::new(NowPlaying::new()));
-    let (now_playing_body, down_button, body_swipe_gesture) = body::create(now_playing.clone());
+    let queue_view = QueueView::new(state.clone());
+    let (now_playing_body, down_button, body_swipe_gesture)
+        = body::create(now_playing.clone(), &queue_view.scrolled_window);
     let (bottom_widget, bottom_swipe_gesture, image_click) = bottom_widget::create(now_playing.clone());
     let realize_bottom = {
         let now_playing = now_playing.clone();
@@ -138,6 +147,83 @@ pub fn create(state: Rc<State>) -> (gtk::Box, gtk::Box, Rc<RefCell<NowPlaying>>)
                 now_playing.clone());
         }
     });
+    let Config { repeat_mode: current_repeat_mode, shuffle_seed, volume, mute, .. }
+        = config.get_result::<Config>(&mut get_connection()).unwrap();
+    now_playing.borrow().volume.connect_value_changed({
+        let mpris_player = mpris_player.clone();
+        move |volume| {
+            PLAYBIN.set_property(VOLUME, volume.value());
+            mpris_player.set_volume(volume.value());
+        }
+    });
+    now_playing.borrow().mute.connect_toggled({
+        let now_playing = now_playing.clone();
+        move |mute| {
+            PLAYBIN.set_property(MUTE, mute.is_active());
+            now_playing.borrow().set_mute(mute.is_active());
+        }
+    });
+    mpris_player.connect_volume({
+        let now_playing = now_playing.clone();
+        move |volume| { now_playing.borrow().volume.set_value(volume.clamp(0.0, 1.0)); }
+    });
+    now_playing.borrow().rate.connect_value_changed({
+        let now_playing = now_playing.clone();
+        let mpris_player = mpris_player.clone();
+        move |rate| {
+            let stop = now_playing.borrow().ab_loop.range().map(|(_, end)| { end });
+            if let Err(error) = PLAYBIN.set_rate(rate.value(), stop) {
+                warn!("error changing rate to [{}] [{error}]", rate.value());
+            }
+            mpris_player.set_rate(rate.value());
+        }
+    });
+    mpris_player.connect_rate({
+        let now_playing = now_playing.clone();
+        move |rate| { now_playing.borrow().rate.set_value(rate.clamp(MINIMUM_RATE, MAXIMUM_RATE)); }
+    });
+    mpris_player.set_rate(1.0);
+    PLAYBIN.set_property(VOLUME, volume);
+    mpris_player.set_volume(volume);
+    now_playing.borrow().volume.set_value(volume);
+    now_playing.borrow().mute.set_active(mute == 1);
+    now_playing.borrow_mut().set_repeat_mode(current_repeat_mode);
+    mpris_player.set_loop_status(loop_status(current_repeat_mode));
+    now_playing.borrow().shuffle.set_active(shuffle_seed.is_some());
+    mpris_player.set_shuffle(shuffle_seed.is_some());
+    let change_repeat_mode = Rc::new({
+        let now_playing = now_playing.clone();
+        let mpris_player = mpris_player.clone();
+        move |repeat_mode: RepeatMode| {
+            update_repeat_mode(repeat_mode);
+            now_playing.borrow_mut().set_repeat_mode(repeat_mode);
+            mpris_player.set_loop_status(loop_status(repeat_mode));
+        }
+    });
+    now_playing.borrow().repeat.connect_clicked({
+        let now_playing = now_playing.clone();
+        let change_repeat_mode = change_repeat_mode.clone();
+        move |_| {
+            let next_repeat_mode = match now_playing.borrow().repeat_mode {
+                RepeatMode::Off => { RepeatMode::All }
+                RepeatMode::All => { RepeatMode::One }
+                RepeatMode::One => { RepeatMode::Off }
+            };
+            change_repeat_mode(next_repeat_mode);
+        }
+    });
+    mpris_player.connect_loop_status(move |status| { change_repeat_mode(repeat_mode(status)); });
+    now_playing.borrow().shuffle.connect_toggled({
+        let mpris_player = mpris_player.clone();
+        move |shuffle| {
+            set_shuffle(shuffle.is_active());
+            mpris_player.set_shuffle(shuffle.is_active());
+        }
+    });
+    mpris_player.connect_shuffle({
+        let now_playing = now_playing.clone();
+        move |shuffle| { now_playing.borrow().shuffle.set_active(shuffle); }
+    });
     let tracking_position = Rc::new(Cell::new(false));
     let once = Once::new();
     state.window_actions.song_selected.action.connect_activate({
@@ -194,6 +280,7 @@ pub fn create(state: Rc<State>) -> (gtk::Box, gtk::Box, Rc<RefCell<NowPlaying>>)
                         now_playing.borrow_mut().set_duration();
                     }
                     DurationChanged(_) => { now_playing.borrow_mut().set_duration(); }
+                    SegmentDone(_) => { now_playing.borrow().ab_loop.repeat(); }
                     StreamStart(_) => {
                         let uri = &PLAYBIN.property::<String>("current-uri")[5.. /* remove "file:" */];
                         get_connection().transaction(|connection| {
@@ -201,9 +288,23 @@ pub fn create(state: Rc<State>) -> (gtk::Box, gtk::Box, Rc<RefCell<NowPlaying>>)
                                 .filter(path.concat("/").concat(song_path).eq(uri))
                                 .get_result::<(Collection, Song)>(connection)?;
                             update(config).set(current_song_id.eq(song.id)).execute(connection)?;
+                            let Config { current_queue_id, shuffle_seed, .. }
+                                = config.get_result::<Config>(connection)?;
+                            PLAYBIN.set_replay_gain(&song, current_queue_id.is_none() && shuffle_seed.is_none());
+                            decide();
+                            now_playing.borrow().ab_loop.set_song(song.id);
+                            // a new song starts at normal rate
+                            let rate = now_playing.borrow().rate.value();
+                            if rate != 1.0 {
+                                if let Err(error) = PLAYBIN.set_rate(rate, None) {
+                                    warn!("error changing rate to [{rate}] [{error}]");
+                                }
+                            }
                             let title = song.title_str().to_owned();
                             now_playing.borrow_mut().set_song_info(&title, or_none(&song.artist));
-                            let cover = (&song, &collection).path().cover();
+                            let song_path = (&song, &collection).path();
+                            now_playing.borrow().lyrics.show(&song, &song_path);
+                            let cover = song_path.cover();
                             let art_url = now_playing.borrow_mut().set_album_image(cover);
                             state.window_actions.stream_started.activate(song.id);
                             mpris_player.set_metadata(Metadata {
@@ -2,14 +2,18 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use gstreamer::prelude::ElementExt;
 use gstreamer::State::Null;
-use mpris_player::MprisPlayer;
+use mpris_player::{LoopStatus, MprisPlayer};
 use crate::common::constant::APP_ID;
+use crate::config::RepeatMode;
 use crate::now_playing::playbin::{PLAYBIN, Playbin};
 
+pub(super) const MINIMUM_RATE: f64 = 0.5;
+pub(super) const MAXIMUM_RATE: f64 = 1.5;
+
 pub(super) fn mpris_player() -> Arc<MprisPlayer> {
     let mpris_player = MprisPlayer::new("harborz".to_string(), "Harborz".to_string(), APP_ID.to_string());
     mpris_player.set_can_quit(false);
-    mpris_player.set_can_control(false);
+    mpris_player.set_can_control(true);
     mpris_player.set_can_raise(false);
     mpris_player.set_can_play(true);
     mpris_player.set_can_pause(true);
@@ -17,6 +21,8 @@ pub(super) fn mpris_player() -> Arc<MprisPlayer> {
     mpris_player.set_can_go_next(true);
     mpris_player.set_can_go_previous(true);
     mpris_player.set_can_set_fullscreen(false);
+    mpris_player.set_minimum_rate(MINIMUM_RATE);
+    mpris_player.set_maximum_rate(MAXIMUM_RATE);
     mpris_player.connect_stop(move || {
         PLAYBIN.set_uri(&PathBuf::from(""));
         PLAYBIN.set_state(Null).unwrap();
@@ -25,3 +31,19 @@ pub(super) fn mpris_player() -> Arc<MprisPlayer> {
     mpris_player.connect_previous(|| { PLAYBIN.go_delta_song(-1, true) });
     mpris_player
 }
+
+pub(super) fn loop_status(repeat_mode: RepeatMode) -> LoopStatus {
+    match repeat_mode {
+        RepeatMode::Off => { LoopStatus::None }
+        RepeatMode::One => { LoopStatus::Track }
+        RepeatMode::All => { LoopStatus::Playlist }
+    }
+}
+
+pub(super) fn repeat_mode(loop_status: LoopStatus) -> RepeatMode {
+    match loop_status {
+        LoopStatus::None => { RepeatMode::Off }
+        LoopStatus::Track => { RepeatMode::One }
+        LoopStatus::Playlist => { RepeatMode::All }
+    }
+}
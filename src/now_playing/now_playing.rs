@@ -5,11 +5,16 @@ use adw::prelude::*;
 use adw::WindowTitle;
 use gstreamer::ClockTime;
 use gstreamer::prelude::ElementExtManual;
-use gtk::{Button, Image, Label, ProgressBar, Scale};
+use gtk::{Button, Image, Label, PositionType, ProgressBar, Scale, ToggleButton};
 use gtk::Align::{End, Start};
 use crate::common::{ImagePathBuf, SONG_ICON, StyledLabelBuilder, StyledWidget};
 use crate::common::state::State;
 use crate::common::util::{format, format_pad};
+use crate::config::RepeatMode;
+use crate::now_playing::ab_loop::AbLoop;
+use crate::now_playing::crossfade::Crossfader;
+use crate::now_playing::lyrics::LyricsView;
+use crate::now_playing::mpris::{MAXIMUM_RATE, MINIMUM_RATE};
 use crate::now_playing::playbin::PLAYBIN;
 
 pub(super) struct PlayPauseInfo {
@@ -66,6 +71,15 @@ pub struct NowPlaying {
     pub body_song: Label,
     pub bottom_artist: Label,
     pub body_artist: Label,
+    pub repeat_mode: RepeatMode,
+    pub repeat: Button,
+    pub shuffle: ToggleButton,
+    pub volume: Scale,
+    pub mute: ToggleButton,
+    pub rate: Scale,
+    pub lyrics: Rc<LyricsView>,
+    pub crossfader: Rc<Crossfader>,
+    pub ab_loop: Rc<AbLoop>,
 }
 
 impl NowPlaying {
@@ -76,6 +90,16 @@ impl NowPlaying {
     pub(super) fn new() -> Self {
         let scale = Scale::builder().hexpand(true).name("small-slider").build();
         scale.set_range(0.0, 1.0);
+        let volume = Scale::builder().hexpand(true).name("small-slider").tooltip_text("Volume").build();
+        volume.set_range(0.0, 1.0);
+        volume.set_increments(0.05, 0.1);
+        let rate = Scale::builder().hexpand(true).width_request(160).draw_value(true).digits(2).tooltip_text("Speed")
+            .build();
+        rate.set_range(MINIMUM_RATE, MAXIMUM_RATE);
+        rate.set_increments(0.05, 0.1);
+        rate.set_value(1.0);
+        rate.add_mark(1.0, PositionType::Bottom, None);
+        let ab_loop = AbLoop::new(&scale, &rate);
         NowPlaying {
             cover: None,
             bottom_image: Image::builder().pixel_size(56).build(),
@@ -99,6 +123,16 @@ impl NowPlaying {
             body_song: Label::builder().ellipsized().build().with_css_class("title-3"),
             bottom_artist: Label::builder().ellipsized().build(),
             body_artist: Label::builder().ellipsized().build(),
+            repeat_mode: RepeatMode::Off,
+            repeat: Button::builder().hexpand(true).build().flat(),
+            shuffle: ToggleButton::builder().icon_name("media-playlist-shuffle").tooltip_text("Shuffle").hexpand(true)
+                .build().flat(),
+            volume,
+            mute: ToggleButton::builder().icon_name("audio-volume-high-symbolic").tooltip_text("Mute").build().flat(),
+            rate,
+            lyrics: LyricsView::new(),
+            crossfader: Crossfader::new(),
+            ab_loop,
         }
     }
     pub(super) fn click_play_pause(&self) {
@@ -175,8 +209,24 @@ impl NowPlaying {
         self.update_image(false);
         result
     }
+    pub fn set_repeat_mode(&mut self, repeat_mode: RepeatMode) {
+        let (icon_name, tooltip) = match repeat_mode {
+            RepeatMode::Off => { ("media-playlist-consecutive", "Repeat off") }
+            RepeatMode::One => { ("media-playlist-repeat-song", "Repeat one") }
+            RepeatMode::All => { ("media-playlist-repeat", "Repeat all") }
+        };
+        self.repeat_mode = repeat_mode;
+        self.repeat.set_icon_name(icon_name);
+        self.repeat.set_tooltip_text(Some(tooltip));
+    }
+    pub fn set_mute(&self, mute: bool) {
+        self.mute.set_icon_name(if mute { "audio-volume-muted-symbolic" } else { "audio-volume-high-symbolic" });
+    }
     pub fn set_position(&mut self, position: u64) {
         self.position = position;
         self.update_position(false);
+        self.lyrics.set_position(position);
+        // a loop never reaches the end of the song to fade out of
+        if self.ab_loop.range().is_none() { self.crossfader.on_position(position, self.duration, self.rate.value()); }
     }
 }
@@ -2,28 +2,47 @@ use std::cell::RefCell;
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::time::Duration;
-use diesel::{Connection, QueryDsl, RunQueryDsl};
-use gstreamer::{ClockTime, ElementFactory, Pipeline, SeekFlags};
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, update};
+use gstreamer::{Bin, ClockTime, Element, ElementFactory, parse_bin_from_description, Pipeline, SeekFlags, SeekType};
 use gstreamer::glib::{Cast, ObjectExt};
-use gstreamer::prelude::{ElementExt, ElementExtManual};
+use gstreamer::prelude::{ElementExt, ElementExtManual, GstBinExt};
 use gstreamer::State::*;
 use log::warn;
 use once_cell::sync::Lazy;
 use crate::body::collection::model::Collection;
 use crate::config::Config;
 use crate::db::get_connection;
+use crate::now_playing::crossfade::ends_in_crossfade;
 use crate::now_playing::now_playing::NowPlaying;
+use crate::queue::delta_song;
 use crate::schema::collections::dsl::collections;
-use crate::schema::config::current_song_id;
+use crate::schema::config::current_queue_id;
 use crate::schema::config::dsl::config;
-use crate::schema::songs::{album, artist};
 use crate::schema::songs::dsl::songs;
-use crate::song::{get_current_album, Song};
+use crate::song::Song;
 use crate::song::WithPath;
 
 pub(super) const URI: &'static str = "uri";
-pub static PLAYBIN: Lazy<Pipeline> = Lazy::new(|| {
+pub const VOLUME: &'static str = "volume";
+pub const MUTE: &'static str = "mute";
+const AUDIO_FILTER: &'static str = "audio-filter";
+const RGVOLUME: &'static str = "rgvolume";
+const RGLIMITER: &'static str = "rglimiter";
+const FADE: &'static str = "fade";
+
+pub(super) fn playbin() -> Pipeline {
     let playbin = ElementFactory::make("playbin3").build().unwrap().downcast::<Pipeline>().unwrap();
+    match parse_bin_from_description(
+        &format!("audioconvert ! {RGVOLUME} name={RGVOLUME} ! {RGLIMITER} name={RGLIMITER} ! scaletempo \
+            ! {VOLUME} name={FADE}"), true) {
+        Ok(audio_filter) => { playbin.set_property(AUDIO_FILTER, &audio_filter); }
+        Err(error) => { warn!("error creating ReplayGain audio filter [{error}]"); }
+    }
+    playbin
+}
+
+pub static PLAYBIN: Lazy<Pipeline> = Lazy::new(|| {
+    let playbin = playbin();
     if let Ok((song, collection, _)) = songs.inner_join(collections).inner_join(config)
         .get_result::<(Song, Collection, Config)>(&mut get_connection()) {
         playbin.set_uri(&(&song, &collection).path());
@@ -34,13 +53,47 @@ pub static PLAYBIN: Lazy<Pipeline> = Lazy::new(|| {
     playbin.connect("about-to-finish", true, {
         let playbin = playbin.clone();
         move |_| {
-            playbin.go_delta_song(1, false);
+            // a crossfade starts the next song itself before this one ends, unless it missed its moment
+            if !ends_in_crossfade() { playbin.go_delta_song(1, false); }
             None
         }
     });
     playbin
 });
 
+fn audio_filter(pipeline: &Pipeline) -> Option<Bin> {
+    pipeline.property::<Option<Element>>(AUDIO_FILTER).and_then(|it| { it.downcast::<Bin>().ok() })
+}
+
+#[derive(Debug, PartialEq)]
+enum ReplayGainMode {
+    Off,
+    Track,
+    Album,
+}
+
+impl ReplayGainMode {
+    fn of(song: &Song, following_album: bool) -> Self {
+        match (song.track_gain, song.album_gain) {
+            (None, None) => { ReplayGainMode::Off }
+            (None, Some(_)) => { ReplayGainMode::Album }
+            (Some(_), Some(_)) if following_album => { ReplayGainMode::Album }
+            (Some(_), _) => { ReplayGainMode::Track }
+        }
+    }
+    // lowered so that the peak does not clip
+    fn gain(&self, song: &Song) -> f64 {
+        let (gain, peak) = match self {
+            ReplayGainMode::Off => { return 0.0; }
+            ReplayGainMode::Track => { (song.track_gain, song.track_peak) }
+            ReplayGainMode::Album => { (song.album_gain, song.album_peak) }
+        };
+        let gain = gain.unwrap_or(0.0);
+        let limited = peak.filter(|it| { *it > 0.0 }).map(|it| { gain.min(-20.0 * it.log10()) }).unwrap_or(gain);
+        limited.clamp(-60.0, 60.0)
+    }
+}
+
 pub trait Playbin {
     fn set_uri_str(&self, uri: &str);
     fn set_uri(&self, uri: &PathBuf);
@@ -48,6 +101,10 @@ pub trait Playbin {
     fn seek_internal(&self, value: u64, now_playing: Rc<RefCell<NowPlaying>>) -> anyhow::Result<()>;
     fn simple_seek(&self, delta: Duration, forward: bool, now_playing: Rc<RefCell<NowPlaying>>);
     fn go_delta_song(&self, delta: i32, now: bool);
+    fn seek_range(&self, rate: f64, flags: SeekFlags, position: u64, stop: Option<u64>) -> anyhow::Result<()>;
+    fn set_rate(&self, rate: f64, stop: Option<u64>) -> anyhow::Result<()>;
+    fn set_replay_gain(&self, song: &Song, following_album: bool);
+    fn set_fade(&self, volume: f64);
 }
 
 impl Playbin for Pipeline {
@@ -61,7 +118,11 @@ impl Playbin for Pipeline {
         PLAYBIN.query_position().map(ClockTime::nseconds)
     }
     fn seek_internal(&self, value: u64, now_playing: Rc<RefCell<NowPlaying>>) -> anyhow::Result<()> {
-        self.seek_simple(SeekFlags::FLUSH | SeekFlags::KEY_UNIT, ClockTime::from_nseconds(value))?;
+        let (rate, stop) = {
+            let now_playing = now_playing.borrow();
+            (now_playing.rate.value(), now_playing.ab_loop.stop_at(value))
+        };
+        self.seek_range(rate, SeekFlags::FLUSH | SeekFlags::KEY_UNIT, value, stop)?;
         Ok(now_playing.borrow_mut().set_position(value))
     }
     fn simple_seek(&self, delta: Duration, forward: bool, now_playing: Rc<RefCell<NowPlaying>>) {
@@ -75,22 +136,44 @@ impl Playbin for Pipeline {
     }
     fn go_delta_song(&self, delta: i32, now: bool) {
         get_connection().transaction(|connection| {
-            if let Ok((Some(current_song_id_int), artist_string, album_string)) = config.inner_join(songs)
-                .select((current_song_id, artist, album))
-                .get_result::<(Option<i32>, Option<String>, Option<String>)>(connection) {
-                let song_collections
-                    = get_current_album(&artist_string.map(Rc::new), &album_string.map(Rc::new), connection);
-                let delta_song_index = song_collections.iter().position(|(song, _)| { song.id == current_song_id_int })
-                    .unwrap() as i32 + delta;
-                if delta_song_index >= 0 && delta_song_index < song_collections.len() as i32 {
-                    let (delta_song, delta_collection) = &song_collections[delta_song_index as usize];
-                    let playing = self.current_state() == Playing;
-                    if now { self.set_state(Null).unwrap(); }
-                    self.set_uri(&(delta_song, delta_collection).path());
-                    if now { self.set_state(if playing { Playing } else { Paused }).unwrap(); }
-                }
+            if let Some((delta_song, delta_collection, delta_queue_id)) = delta_song(delta, !now, connection)? {
+                update(config).set(current_queue_id.eq(delta_queue_id)).execute(connection)?;
+                let playing = self.current_state() == Playing;
+                if now { self.set_state(Null).unwrap(); }
+                self.set_uri(&(&delta_song, &delta_collection).path());
+                if now { self.set_state(if playing { Playing } else { Paused }).unwrap(); }
             }
             anyhow::Ok(())
         }).unwrap();
     }
+    fn seek_range(&self, rate: f64, flags: SeekFlags, position: u64, stop: Option<u64>) -> anyhow::Result<()> {
+        let flags = if stop.is_some() { flags | SeekFlags::SEGMENT } else { flags };
+        let stop_type = if stop.is_some() { SeekType::Set } else { SeekType::None };
+        self.seek(rate, flags, SeekType::Set, ClockTime::from_nseconds(position), stop_type,
+            stop.map(ClockTime::from_nseconds))?;
+        Ok(())
+    }
+    fn set_rate(&self, rate: f64, stop: Option<u64>) -> anyhow::Result<()> {
+        if let Some(position) = self.get_position() {
+            self.seek_range(rate, SeekFlags::FLUSH | SeekFlags::ACCURATE, position, stop)?;
+        }
+        Ok(())
+    }
+    fn set_replay_gain(&self, song: &Song, following_album: bool) {
+        if let Some(audio_filter) = audio_filter(self) {
+            let mode = ReplayGainMode::of(song, following_album);
+            if let Some(rgvolume) = audio_filter.by_name(RGVOLUME) {
+                rgvolume.set_property("album-mode", mode == ReplayGainMode::Album);
+                rgvolume.set_property("fallback-gain", mode.gain(song));
+            }
+            if let Some(rglimiter) = audio_filter.by_name(RGLIMITER) {
+                rglimiter.set_property("enabled", mode != ReplayGainMode::Off);
+            }
+        }
+    }
+    fn set_fade(&self, volume: f64) {
+        if let Some(fade) = audio_filter(self).and_then(|it| { it.by_name(FADE) }) {
+            fade.set_property(VOLUME, volume);
+        }
+    }
 }
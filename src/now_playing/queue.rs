@@ -0,0 +1,105 @@
+use std::rc::Rc;
+use adw::prelude::*;
+use diesel::RunQueryDsl;
+use gtk::{Button, GestureClick, Label, ScrolledWindow, Separator};
+use gtk::Orientation::Vertical;
+use crate::common::{StyledLabelBuilder, StyledWidget};
+use crate::common::constant::{DESTRUCTIVE_ACTION, INSENSITIVE_FG};
+use crate::common::state::State;
+use crate::common::util::or_none;
+use crate::config::Config;
+use crate::db::get_connection;
+use crate::queue::{clear_queue, follow_queue, get_queue, move_queue_entry, remove_queue_entry};
+use crate::schema::config::dsl::config;
+use crate::song::WithPath;
+
+pub(super) struct QueueView {
+    pub(super) scrolled_window: ScrolledWindow,
+    state: Rc<State>,
+}
+
+impl QueueView {
+    pub(super) fn new(state: Rc<State>) -> Rc<Self> {
+        let this = Rc::new(Self {
+            scrolled_window: ScrolledWindow::builder().vexpand(true).visible(false).build(),
+            state,
+        });
+        this.scrolled_window.connect_map({
+            let this = this.clone();
+            move |_| { this.render(); }
+        });
+        this.state.window_actions.stream_started.action.connect_activate({
+            let this = this.clone();
+            move |_, _| { if this.scrolled_window.is_mapped() { this.render(); } }
+        });
+        this
+    }
+    fn render(self: &Rc<Self>) {
+        let Config { current_queue_id, .. } = config.get_result::<Config>(&mut get_connection()).unwrap();
+        let queue_box = gtk::Box::builder().orientation(Vertical).build();
+        let queue = get_queue(&mut get_connection());
+        if queue.is_empty() {
+            queue_box.append(&Label::builder().label("Queue is empty").name(INSENSITIVE_FG).margin_top(12).build());
+        }
+        for (queue_entry, (song, collection)) in &queue {
+            let row = gtk::Box::builder().spacing(4).margin_end(4).build();
+            queue_box.append(&row);
+            queue_box.append(&Separator::builder().build());
+            let song_box = gtk::Box::builder().orientation(Vertical)
+                .margin_start(8).margin_top(8).margin_bottom(8).build();
+            row.append(&song_box);
+            let title_label = Label::builder().label(song.title_str()).ellipsized().build();
+            if current_queue_id == Some(queue_entry.id) { title_label.add_css_class("accent"); }
+            song_box.append(&title_label);
+            song_box.append(&Label::builder().label(or_none(&song.artist)).ellipsized().subscript()
+                .name(INSENSITIVE_FG).build());
+            let gesture_click = GestureClick::new();
+            gesture_click.connect_released({
+                let state = self.state.clone();
+                let queue_id = queue_entry.id;
+                let path = (song, collection).path();
+                move |_, _, _, _| {
+                    follow_queue(queue_id);
+                    state.window_actions.song_selected.activate(path.to_str().unwrap());
+                }
+            });
+            song_box.add_controller(gesture_click);
+            for (icon_name, tooltip, delta) in [("go-up", "Move up", -1), ("go-down", "Move down", 1)] {
+                let move_button = Button::builder().icon_name(icon_name).tooltip_text(tooltip).build().flat();
+                row.append(&move_button);
+                move_button.connect_clicked({
+                    let this = self.clone();
+                    let queue_id = queue_entry.id;
+                    move |_| {
+                        move_queue_entry(queue_id, delta);
+                        this.render();
+                    }
+                });
+            }
+            let remove_button = Button::builder().icon_name("list-remove").tooltip_text("Remove").build().flat()
+                .with_css_class(DESTRUCTIVE_ACTION);
+            row.append(&remove_button);
+            remove_button.connect_clicked({
+                let this = self.clone();
+                let queue_id = queue_entry.id;
+                move |_| {
+                    remove_queue_entry(queue_id);
+                    this.render();
+                }
+            });
+        }
+        if !queue.is_empty() {
+            let clear_button = Button::builder().label("Clear queue").margin_top(8).margin_bottom(8)
+                .margin_start(8).margin_end(8).build().with_css_class(DESTRUCTIVE_ACTION);
+            queue_box.append(&clear_button);
+            clear_button.connect_clicked({
+                let this = self.clone();
+                move |_| {
+                    clear_queue();
+                    this.render();
+                }
+            });
+        }
+        self.scrolled_window.set_child(Some(&queue_box));
+    }
+}
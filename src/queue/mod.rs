@@ -0,0 +1,281 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+use diesel::{Connection, delete, ExpressionMethods, insert_into, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    SqliteConnection, update};
+use diesel::dsl::max;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use crate::body::collection::model::Collection;
+use crate::config::{Config, RepeatMode};
+use crate::db::get_connection;
+use crate::schema::collections::dsl::collections;
+use crate::schema::config::{current_queue_id, shuffle_seed, shuffle_start};
+use crate::schema::config::dsl::config;
+use crate::schema::queue::{id, position, song_id};
+use crate::schema::queue::dsl::queue;
+use crate::schema::songs::dsl::songs;
+use crate::song::{get_current_album, Song};
+
+#[derive(diesel::Queryable, diesel::Selectable, Debug)]
+#[diesel(table_name = crate::schema::queue)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Queue {
+    pub id: i32,
+    pub song_id: i32,
+    pub position: i32,
+}
+
+#[derive(Clone, Copy)]
+pub enum Enqueue {
+    Next,
+    Last,
+}
+
+pub fn get_queue(connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> Vec<(Queue, (Song, Collection))> {
+    queue.inner_join(songs.inner_join(collections)).order_by(position)
+        .get_results::<(Queue, (Song, Collection))>(connection).unwrap()
+}
+
+fn insert_at(song_ids: &Vec<i32>, start: i32, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> QueryResult<usize> {
+    update(queue.filter(position.ge(start))).set(position.eq(position + song_ids.len() as i32)).execute(connection)?;
+    insert_into(queue).values(song_ids.iter().enumerate().map(|(i, song_id_int)| {
+        (song_id.eq(song_id_int), position.eq(start + i as i32))
+    }).collect::<Vec<_>>()).execute(connection)
+}
+
+pub fn enqueue(song_ids: Vec<i32>, enqueue: Enqueue) {
+    get_connection().transaction(|connection| {
+        let Config { current_song_id: current_song_id_option, current_queue_id: current_queue_id_option, .. }
+            = config.get_result::<Config>(connection)?;
+        match enqueue {
+            Enqueue::Last => {
+                let start = queue.select(max(position)).get_result::<Option<i32>>(connection)?
+                    .map(|it| { it + 1 }).unwrap_or(0);
+                insert_at(&song_ids, start, connection)?;
+            }
+            Enqueue::Next => {
+                if let Some(current_queue_id_int) = current_queue_id_option {
+                    let current_position = queue.find(current_queue_id_int).select(position)
+                        .get_result::<i32>(connection)?;
+                    insert_at(&song_ids, current_position + 1, connection)?;
+                } else if let Some(current_song_id_int) = current_song_id_option {
+                    // playing from an album, so the current song is put in front of the queue to be followed from
+                    insert_at(&song_ids, 0, connection)?;
+                    update(queue).set(position.eq(position + 1)).execute(connection)?;
+                    let current_queue_id_int = insert_into(queue)
+                        .values((song_id.eq(current_song_id_int), position.eq(0))).returning(id)
+                        .get_result::<i32>(connection)?;
+                    update(config).set(current_queue_id.eq(current_queue_id_int)).execute(connection)?;
+                } else {
+                    insert_at(&song_ids, 0, connection)?;
+                }
+            }
+        }
+        anyhow::Ok(())
+    }).unwrap();
+}
+
+pub fn move_queue_entry(queue_id: i32, delta: i32) {
+    get_connection().transaction(|connection| {
+        let current_position = queue.find(queue_id).select(position).get_result::<i32>(connection)?;
+        let statement = queue.select((id, position)).into_boxed();
+        if let Some((other_id, other_position)) = if delta > 0 {
+            statement.filter(position.gt(current_position)).order_by(position)
+        } else {
+            statement.filter(position.lt(current_position)).order_by(position.desc())
+        }.first::<(i32, i32)>(connection).optional()? {
+            update(queue.find(other_id)).set(position.eq(current_position)).execute(connection)?;
+            update(queue.find(queue_id)).set(position.eq(other_position)).execute(connection)?;
+        }
+        anyhow::Ok(())
+    }).unwrap();
+}
+
+pub fn remove_queue_entry(queue_id: i32) {
+    delete(queue.find(queue_id)).execute(&mut get_connection()).unwrap();
+}
+
+pub fn clear_queue() {
+    delete(queue).execute(&mut get_connection()).unwrap();
+}
+
+pub fn follow_album() {
+    update(config).set(current_queue_id.eq(None::<i32>)).execute(&mut get_connection()).unwrap();
+}
+
+pub fn follow_queue(queue_id: i32) {
+    update(config).set(current_queue_id.eq(queue_id)).execute(&mut get_connection()).unwrap();
+}
+
+pub fn set_shuffle(shuffle: bool) {
+    get_connection().transaction(|connection| {
+        let Config { current_song_id: current_song_id_option, .. } = config.get_result::<Config>(connection)?;
+        update(config).set(if shuffle {
+            (shuffle_seed.eq(Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as i64)),
+                shuffle_start.eq(current_song_id_option))
+        } else {
+            (shuffle_seed.eq(None::<i64>), shuffle_start.eq(None::<i32>))
+        }).execute(connection)?;
+        anyhow::Ok(())
+    }).unwrap();
+}
+
+fn queue_entries(connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> Vec<(Song, Collection, Option<i32>)> {
+    get_queue(connection).into_iter()
+        .map(|(queue_entry, (song, collection))| { (song, collection, Some(queue_entry.id)) }).collect()
+}
+
+fn shuffle(entries: &mut Vec<(Song, Collection, Option<i32>)>, seed: i64, start: Option<i32>) {
+    entries.sort_by_cached_key(|(song, _, queue_id)| {
+        let key = queue_id.unwrap_or(song.id);
+        let mut hasher = DefaultHasher::new();
+        (seed, key).hash(&mut hasher);
+        // the song playing when shuffle was turned on stays first, in the album and in the queue alike
+        (start != Some(song.id), hasher.finish())
+    });
+}
+
+fn wrap(index: i32, len: i32, repeat_all: bool) -> Option<usize> {
+    if index >= 0 && index < len {
+        Some(index as usize)
+    } else if repeat_all && len > 0 {
+        Some(index.rem_euclid(len) as usize)
+    } else {
+        None
+    }
+}
+
+pub fn delta_song(delta: i32, auto: bool, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> anyhow::Result<Option<(Song, Collection, Option<i32>)>> {
+    let (Config {
+        current_queue_id: current_queue_id_option, repeat_mode, shuffle_seed: seed, shuffle_start: start, ..
+    }, (current_song, current_collection)) = match config.inner_join(songs.inner_join(collections))
+        .get_result::<(Config, (Song, Collection))>(connection).optional()? {
+        Some(current) => { current }
+        None => { return Ok(None); }
+    };
+    if auto && repeat_mode == RepeatMode::One {
+        return Ok(Some((current_song, current_collection, current_queue_id_option)));
+    }
+    let ordered = |mut entries: Vec<(Song, Collection, Option<i32>)>| {
+        if let Some(seed) = seed { shuffle(&mut entries, seed, start); }
+        entries
+    };
+    let mut entries = ordered(if current_queue_id_option.is_some() {
+        queue_entries(connection)
+    } else {
+        get_current_album(current_song.album_id, connection).into_iter()
+            .map(|(song, collection)| { (song, collection, None) }).collect()
+    });
+    // a current song missing from the entries, e.g. removed from the queue while playing, continues from the start
+    let delta_index = entries.iter().position(|(song, _, queue_id)| {
+        if current_queue_id_option.is_some() {
+            *queue_id == current_queue_id_option
+        } else {
+            song.id == current_song.id
+        }
+    }).map(|it| { it as i32 }).unwrap_or(-1) + delta;
+    let len = entries.len() as i32;
+    let first_queue_entry = if current_queue_id_option.is_none() && delta_index >= len {
+        ordered(queue_entries(connection)).into_iter().next()
+    } else {
+        None
+    };
+    Ok(if delta_index >= 0 && delta_index < len {
+        Some(entries.swap_remove(delta_index as usize))
+    } else if first_queue_entry.is_some() {
+        first_queue_entry
+    } else {
+        wrap(delta_index, len, repeat_mode == RepeatMode::All).map(|i| { entries.swap_remove(i) })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::body::collection::model::Collection;
+    use crate::queue::{shuffle, wrap};
+    use crate::song::Song;
+
+    fn entry(song_id: i32, queue_id: Option<i32>) -> (Song, Collection, Option<i32>) {
+        (Song {
+            id: song_id,
+            path: format!("{song_id}.flac"),
+            collection_id: 1,
+            title: None,
+            artist: None,
+            album: None,
+            year: None,
+            genre: None,
+            track_number: Some(song_id),
+            album_volume: None,
+            album_artist: None,
+            duration: 0,
+            lyrics: None,
+            album_id: Some(1),
+            track_gain: None,
+            track_peak: None,
+            album_gain: None,
+            album_peak: None,
+            analysis_failed: 0,
+        }, Collection { id: 1, path: String::from("/music"), modified: None }, queue_id)
+    }
+
+    fn song_ids(entries: &[(Song, Collection, Option<i32>)]) -> Vec<i32> {
+        entries.iter().map(|(song, _, _)| { song.id }).collect()
+    }
+
+    #[test]
+    fn shuffle_plays_the_start_song_first() {
+        for seed in 0..20 {
+            let mut entries = (1..=10).map(|song_id| { entry(song_id, None) }).collect::<Vec<_>>();
+            shuffle(&mut entries, seed, Some(7));
+            assert_eq!(entries[0].0.id, 7);
+        }
+    }
+
+    #[test]
+    fn shuffle_pins_the_start_song_of_the_queue_by_song_id() {
+        for seed in 0..20 {
+            let mut entries = (1..=10).map(|song_id| { entry(song_id, Some(100 + song_id)) }).collect::<Vec<_>>();
+            shuffle(&mut entries, seed, Some(3));
+            assert_eq!(entries[0].0.id, 3);
+        }
+    }
+
+    #[test]
+    fn shuffle_keeps_the_order_of_a_seed() {
+        let shuffled = |seed| {
+            let mut entries = (1..=10).map(|song_id| { entry(song_id, None) }).collect::<Vec<_>>();
+            shuffle(&mut entries, seed, None);
+            song_ids(&entries)
+        };
+        assert_eq!(shuffled(42), shuffled(42));
+        assert_ne!(shuffled(42), (1..=10).collect::<Vec<_>>());
+        let mut sorted = shuffled(42);
+        sorted.sort();
+        assert_eq!(sorted, (1..=10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn wrap_stays_within_the_entries() {
+        assert_eq!(wrap(0, 3, false), Some(0));
+        assert_eq!(wrap(2, 3, true), Some(2));
+    }
+
+    #[test]
+    fn wrap_ends_past_the_last_entry_unless_repeating_all() {
+        assert_eq!(wrap(3, 3, false), None);
+        assert_eq!(wrap(-1, 3, false), None);
+        assert_eq!(wrap(3, 3, true), Some(0));
+        assert_eq!(wrap(-1, 3, true), Some(2));
+    }
+
+    #[test]
+    fn wrap_finds_nothing_in_no_entries() {
+        assert_eq!(wrap(0, 0, true), None);
+        assert_eq!(wrap(1, 0, false), None);
+    }
+}
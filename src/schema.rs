@@ -1,5 +1,18 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    albums (id) {
+        id -> Integer,
+        collection_id -> Integer,
+        artist -> Nullable<Text>,
+        title -> Nullable<Text>,
+        disambiguator -> Text,
+        year -> Nullable<Integer>,
+        cover -> Text,
+        total_tracks -> Nullable<Integer>,
+    }
+}
+
 diesel::table! {
     bodies (id) {
         id -> Integer,
@@ -25,6 +38,69 @@ diesel::table! {
         window_height -> Integer,
         maximized -> Integer,
         now_playing_body_realized -> Integer,
+        current_queue_id -> Nullable<Integer>,
+        repeat_mode -> crate::config::RepeatModeMapping,
+        shuffle_seed -> Nullable<BigInt>,
+        shuffle_start -> Nullable<Integer>,
+        various_artists -> crate::config::VariousArtistsMapping,
+        loudness_analysis -> Integer,
+        write_replay_gain -> Integer,
+        crossfade -> Integer,
+        volume -> Double,
+        mute -> Integer,
+    }
+}
+
+diesel::table! {
+    import_issues (id) {
+        id -> Integer,
+        collection_id -> Integer,
+        path -> Text,
+        reason -> Text,
+        ignored -> Integer,
+    }
+}
+
+diesel::table! {
+    loops (id) {
+        id -> Integer,
+        song_id -> Integer,
+        name -> Text,
+        start_position -> BigInt,
+        end_position -> BigInt,
+    }
+}
+
+diesel::table! {
+    merge_songs (id) {
+        id -> Integer,
+        merge_id -> Integer,
+        song_id -> Integer,
+        original -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    merges (id) {
+        id -> Integer,
+        field -> crate::tag::FieldMapping,
+        value -> Text,
+    }
+}
+
+diesel::table! {
+    providers (name) {
+        name -> Text,
+        position -> Integer,
+        enabled -> Integer,
+    }
+}
+
+diesel::table! {
+    queue (id) {
+        id -> Integer,
+        song_id -> Integer,
+        position -> Integer,
     }
 }
 
@@ -43,15 +119,36 @@ diesel::table! {
         album_artist -> Nullable<Text>,
         duration -> BigInt,
         lyrics -> Nullable<Text>,
+        album_id -> Nullable<Integer>,
+        track_gain -> Nullable<Double>,
+        track_peak -> Nullable<Double>,
+        album_gain -> Nullable<Double>,
+        album_peak -> Nullable<Double>,
+        analysis_failed -> Integer,
     }
 }
 
+diesel::joinable!(albums -> collections (collection_id));
+diesel::joinable!(config -> queue (current_queue_id));
 diesel::joinable!(config -> songs (current_song_id));
+diesel::joinable!(import_issues -> collections (collection_id));
+diesel::joinable!(loops -> songs (song_id));
+diesel::joinable!(merge_songs -> merges (merge_id));
+diesel::joinable!(merge_songs -> songs (song_id));
+diesel::joinable!(queue -> songs (song_id));
+diesel::joinable!(songs -> albums (album_id));
 diesel::joinable!(songs -> collections (collection_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    albums,
     bodies,
     collections,
     config,
+    import_issues,
+    loops,
+    merge_songs,
+    merges,
+    providers,
+    queue,
     songs,
 );
@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use anyhow::anyhow;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection, update};
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use gstreamer::{ClockTime, MessageView, parse_launch, Pipeline};
+use gstreamer::glib::{Cast, ObjectExt};
+use gstreamer::prelude::{ElementExt, GstBinExt};
+use gstreamer::State::{Null, Playing};
+use gstreamer::tags::{TrackGain, TrackPeak};
+use log::{info, warn};
+use crate::body::collection::model::Collection;
+use crate::config::update_loudness_analysis;
+use crate::schema::collections::dsl::collections;
+use crate::schema::songs::*;
+use crate::schema::songs::dsl::songs;
+use crate::song::{Song, tagged_replay_gain, WithPath};
+use crate::tag::write_replay_gain;
+use crate::tag::writer::ReplayGain;
+
+pub enum AnalysisProgress {
+    Start(Arc<AtomicBool>),
+    Fraction(f64),
+    Failed(PathBuf, String),
+    End,
+}
+
+fn analyze(song_path: &Path) -> anyhow::Result<(f64, f64)> {
+    let pipeline = parse_launch("filesrc name=source ! decodebin ! audioconvert ! audioresample \
+        ! rganalysis ! fakesink sync=false")?.downcast::<Pipeline>().unwrap();
+    pipeline.by_name("source").unwrap().set_property("location", song_path.to_str().unwrap());
+    let result = pipeline.set_state(Playing).map_err(anyhow::Error::from).and_then(|_| {
+        let (mut gain, mut peak) = (None, None);
+        for message in pipeline.bus().unwrap().iter_timed(ClockTime::NONE) {
+            match message.view() {
+                MessageView::Tag(tag) => {
+                    let tag_list = tag.tags();
+                    if let Some(tag_value) = tag_list.get::<TrackGain>() { gain = Some(tag_value.get()); }
+                    if let Some(tag_value) = tag_list.get::<TrackPeak>() { peak = Some(tag_value.get()); }
+                }
+                MessageView::Eos(_) => { break; }
+                MessageView::Error(error) => { return Err(anyhow!("{}", error.error())); }
+                _ => {}
+            }
+        }
+        gain.zip(peak).ok_or_else(|| { anyhow!("No loudness found") })
+    });
+    pipeline.set_state(Null)?;
+    result
+}
+
+// track gains combined in the power domain weighted by duration, approximating the album as one stream
+fn combined_gain(album_songs: &[(Song, Collection)]) -> Option<(f64, f64)> {
+    let tracks = album_songs.iter().map(|(song, _)| { song.track_gain.map(|gain| { (song, gain) }) })
+        .collect::<Option<Vec<_>>>()?;
+    let total_duration = tracks.iter().map(|(song, _)| { song.duration.max(1) as f64 }).sum::<f64>();
+    let power = tracks.iter().map(|(song, gain)| { song.duration.max(1) as f64 * 10f64.powf(-gain / 10.0) })
+        .sum::<f64>() / total_duration;
+    let peak = tracks.iter().filter_map(|(song, _)| { song.track_peak }).fold(0.0, f64::max);
+    (!tracks.is_empty()).then(|| { (-10.0 * power.log10(), if peak > 0.0 { peak } else { 1.0 }) })
+}
+
+fn fill_album_gains(write_tags: bool, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> anyhow::Result<()> {
+    let album_ids = songs.filter(album_gain.is_null()).select(album_id).distinct()
+        .get_results::<Option<i32>>(connection)?;
+    for album_id_int in album_ids.into_iter().flatten() {
+        let album_songs = songs.inner_join(collections).filter(album_id.eq(album_id_int))
+            .get_results::<(Song, Collection)>(connection)?;
+        if let Some((gain, peak)) = combined_gain(&album_songs) {
+            update(songs.filter(album_id.eq(album_id_int)).filter(album_gain.is_null()))
+                .set((album_gain.eq(gain), album_peak.eq(peak))).execute(connection)?;
+            if write_tags {
+                for (song, collection) in album_songs.iter().filter(|(song, _)| { song.album_gain.is_none() }) {
+                    let song_path = (song, collection).path();
+                    if let Err(error) = write_replay_gain(&song_path,
+                        &[(ReplayGain::AlbumGain, gain), (ReplayGain::AlbumPeak, peak)]) {
+                        warn!("error writing album gain to [{song_path:?}] [{error}]");
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn analyze_loudness(write_tags: bool, sender: Sender<AnalysisProgress>,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> anyhow::Result<()> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    sender.send(AnalysisProgress::Start(cancel.clone()))?;
+    let pending = songs.inner_join(collections).filter(track_gain.is_null()).filter(analysis_failed.eq(0))
+        .order_by((album_id, album_volume, track_number, id)).get_results::<(Song, Collection)>(connection)?;
+    let total = pending.len();
+    info!("analysing loudness of [{total}] songs");
+    for (i, (song, collection)) in pending.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) { break; }
+        let song_path = (song, collection).path();
+        // songs imported before ReplayGain tags were read keep the gains they carry instead of being decoded
+        if let Some((gain, peak, album_gain_option, album_peak_option)) = tagged_replay_gain(&song_path) {
+            update(songs.find(song.id)).set((track_gain.eq(gain), track_peak.eq(peak),
+                album_gain.eq(album_gain_option), album_peak.eq(album_peak_option))).execute(connection)?;
+            sender.send(AnalysisProgress::Fraction((i + 1) as f64 / total as f64))?;
+            continue;
+        }
+        match analyze(&song_path) {
+            Ok((gain, peak)) => {
+                update(songs.find(song.id)).set((track_gain.eq(gain), track_peak.eq(peak))).execute(connection)?;
+                if write_tags {
+                    if let Err(error) = write_replay_gain(&song_path,
+                        &[(ReplayGain::TrackGain, gain), (ReplayGain::TrackPeak, peak)]) {
+                        warn!("error writing track gain to [{song_path:?}] [{error}]");
+                    }
+                }
+            }
+            Err(error) => {
+                warn!("error analysing loudness of [{song_path:?}] [{error}]");
+                update(songs.find(song.id)).set(analysis_failed.eq(1)).execute(connection)?;
+                sender.send(AnalysisProgress::Failed(song_path, error.to_string()))?;
+            }
+        }
+        sender.send(AnalysisProgress::Fraction((i + 1) as f64 / total as f64))?;
+    }
+    fill_album_gains(write_tags, connection)?;
+    if cancel.load(Ordering::Relaxed) {
+        info!("cancelled analysing loudness");
+    }
+    update_loudness_analysis(false);
+    Ok(sender.send(AnalysisProgress::End)?)
+}
+
+pub fn pending_analysis(connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> i64 {
+    songs.filter(track_gain.is_null()).filter(analysis_failed.eq(0)).count().get_result::<i64>(connection).unwrap()
+}
@@ -1,28 +1,41 @@
 use std::cmp::max;
+use std::collections::HashSet;
+use std::ffi::OsStr;
 use std::fmt::Debug;
+use std::fs::Metadata;
+use std::num::NonZeroUsize;
 use std::ops::Add;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::mpsc::Sender;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use async_std::task;
-use diesel::{ExpressionMethods, insert_into, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection, update};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use diesel::{Connection, delete, ExpressionMethods, insert_into, NullableExpressionMethods, OptionalExtension, QueryDsl,
+    QueryResult, RunQueryDsl, SqliteConnection, update};
+use diesel::dsl::{exists, not};
+use diesel::expression_methods::SqliteExpressionMethods;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
-use gstreamer::ClockTime;
+use gstreamer::{ClockTime, StreamError, TagList};
 use gstreamer::tags::*;
 use gstreamer_pbutils::Discoverer;
-use log::info;
+use log::{info, warn};
 use once_cell::sync::Lazy;
 use walkdir::{DirEntry, WalkDir};
 use crate::body::collection::model::Collection;
+use crate::common::constant::VARIOUS_ARTISTS;
 use crate::common::util::PathString;
 use crate::config::Config;
+use crate::schema::albums;
 use crate::schema::collections::{modified, table as collections};
 use crate::schema::config::dsl::config;
+use crate::schema::import_issues;
 use crate::schema::songs::*;
 use crate::schema::songs::dsl::songs;
 
+pub mod loudness;
+pub mod watch;
+
 #[derive(diesel::Queryable, diesel::Selectable, Debug)]
 #[diesel(table_name = crate::schema::songs)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -40,12 +53,35 @@ pub struct Song {
     pub album_artist: Option<String>,
     pub duration: i64,
     pub lyrics: Option<String>,
+    pub album_id: Option<i32>,
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+    pub analysis_failed: i32,
+}
+
+#[derive(diesel::Queryable, diesel::Selectable, Debug)]
+#[diesel(table_name = crate::schema::albums)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Album {
+    pub id: i32,
+    pub collection_id: i32,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub disambiguator: String,
+    pub year: Option<i32>,
+    pub cover: String,
+    pub total_tracks: Option<i32>,
 }
 
 impl Song {
     pub fn title_str(&self) -> &str {
         self.title.as_deref().unwrap_or(self.path.to_path().file_name().unwrap().to_str().unwrap())
     }
+    fn fingerprint(&self) -> Fingerprint {
+        (self.duration, self.title.clone(), self.artist.clone(), self.album.clone(), self.track_number)
+    }
 }
 
 pub fn join_path(collection_path: &String, song_path: &String) -> PathBuf {
@@ -65,6 +101,7 @@ impl WithPath for (&Song, &Collection) {
 
 pub trait WithImage {
     fn cover(&self) -> PathBuf;
+    fn disc_cover(&self) -> Option<PathBuf>;
     fn logo(&self) -> PathBuf;
     fn sibling_logo(&self) -> PathBuf;
     fn photo(&self) -> PathBuf;
@@ -79,12 +116,22 @@ fn join_grandparent(path_ref: impl AsRef<Path>, file_name: &str) -> PathBuf {
     join_parent(path_ref.as_ref().parent().unwrap(), file_name)
 }
 
+const COVER: &'static str = "cover.jpg";
 const LOGO: &'static str = "logo.jpg";
 const PHOTO: &'static str = "photo.jpg";
 
 impl<P: AsRef<Path>> WithImage for P {
     fn cover(&self) -> PathBuf {
-        join_parent(self, "cover.jpg")
+        let cover = join_parent(self, COVER);
+        if !cover.exists() && self.as_ref().parent().map(is_disc_directory).unwrap_or(false) {
+            join_grandparent(self, COVER)
+        } else {
+            cover
+        }
+    }
+    fn disc_cover(&self) -> Option<PathBuf> {
+        Some(join_parent(self, COVER)).filter(|it| { it.exists() })
+            .filter(|_| { self.as_ref().parent().map(is_disc_directory).unwrap_or(false) })
     }
     fn logo(&self) -> PathBuf {
         join_grandparent(self, LOGO)
@@ -101,10 +148,18 @@ impl<P: AsRef<Path>> WithImage for P {
 }
 
 static DISCOVERER: Lazy<Discoverer> = Lazy::new(|| { Discoverer::new(ClockTime::from_seconds(30)).unwrap() });
+const BATCH_SIZE: usize = 500;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+const FEATURING: [&'static str; 7] = [" feat. ", " feat ", " ft. ", " featuring ", " (feat. ", " (ft. ", " [feat. "];
+const NON_AUDIO_EXTENSIONS: [&'static str; 16]
+    = ["jpg", "jpeg", "png", "gif", "bmp", "webp", "txt", "nfo", "log", "cue", "m3u", "m3u8", "pdf", "sfv", "md5",
+    "lrc"];
 
 pub enum ImportProgress {
-    CollectionStart,
+    CollectionStart(Arc<AtomicBool>),
     Fraction(f64),
+    Skipped(PathBuf),
+    Failed(PathBuf, String),
     CollectionEnd(Arc<RwLock<Collection>>),
 }
 
@@ -121,86 +176,432 @@ impl<'a> StrTag<'a> for Option<&'a TagValue<&str>> {
     }
 }
 
+fn changed(metadata: &Metadata) -> Option<SystemTime> {
+    max(metadata.created().ok(), metadata.modified().ok())
+}
+
 fn walk_newer_than(collection: &Arc<RwLock<Collection>>, last_modified: Option<SystemTime>)
     -> Box<dyn Iterator<Item=walkdir::Result<DirEntry>>> {
     let into_iter = WalkDir::new(&collection.read().unwrap().path).into_iter();
     if let Some(last_modified) = last_modified {
         Box::new(into_iter.filter_entry(move |entry| {
-            let metadata = entry.metadata().unwrap();
-            max(metadata.created().unwrap(), metadata.modified().unwrap()) > last_modified
+            entry.metadata().ok().and_then(|metadata| { changed(&metadata) })
+                .map(|changed| { changed > last_modified }).unwrap_or(true)
         }))
     } else {
         Box::new(into_iter)
     }
 }
 
+struct Discovered {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    year: Option<i32>,
+    genre: Option<String>,
+    track_number: Option<i32>,
+    album_volume: Option<i32>,
+    album_artist: Option<String>,
+    duration: i64,
+    lyrics: Option<String>,
+    total_tracks: Option<i32>,
+    musicbrainz_album_id: Option<String>,
+    track_gain: Option<f64>,
+    track_peak: Option<f64>,
+    album_gain: Option<f64>,
+    album_peak: Option<f64>,
+    changed: Option<SystemTime>,
+}
+
+type Fingerprint = (i64, Option<String>, Option<String>, Option<String>, Option<i32>);
+
+impl Discovered {
+    fn fingerprint(&self) -> Fingerprint {
+        (self.duration, self.title.clone(), self.artist.clone(), self.album.clone(), self.track_number)
+    }
+}
+
+fn discover(discoverer: &Discoverer, entry_path: &Path) -> anyhow::Result<Option<Discovered>> {
+    if entry_path.extension().and_then(OsStr::to_str).map(|extension| {
+        NON_AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+    }).unwrap_or(false) {
+        return Ok(None);
+    }
+    let discoverer_info = match discoverer.discover_uri(format!("file:{}", entry_path.to_str().unwrap()).as_str()) {
+        Ok(discoverer_info) => { discoverer_info }
+        // only files identified as audio that fail to decode are import issues
+        Err(error) if error.matches(StreamError::TypeNotFound) => { return Ok(None); }
+        Err(error) => { return Err(error.into()); }
+    };
+    if !discoverer_info.video_streams().is_empty() || discoverer_info.audio_streams().is_empty() {
+        return Ok(None);
+    }
+    let tag_list = discoverer_info.tags().unwrap_or_else(TagList::new);
+    let string_tag = |tag: Option<TagValue<&str>>| { tag.as_ref().as_str().map(String::from) };
+    Ok(Some(Discovered {
+        title: string_tag(tag_list.get::<Title>()),
+        artist: string_tag(tag_list.get::<Artist>()),
+        album: string_tag(tag_list.get::<Album>()),
+        year: tag_list.get::<DateTime>().map(|it| { it.get().year() }),
+        genre: string_tag(tag_list.get::<Genre>()),
+        track_number: tag_list.get::<TrackNumber>().map(|it| { it.get() as i32 }),
+        album_volume: tag_list.get::<AlbumVolumeNumber>().map(|it| { it.get() as i32 }),
+        album_artist: string_tag(tag_list.get::<AlbumArtist>()),
+        duration: discoverer_info.duration().map(ClockTime::nseconds).unwrap_or(0) as i64,
+        lyrics: string_tag(tag_list.get::<Lyrics>()),
+        total_tracks: tag_list.get::<TrackCount>().map(|it| { it.get() as i32 }),
+        musicbrainz_album_id: string_tag(tag_list.get::<MusicbrainzAlbumid>()),
+        track_gain: tag_list.get::<TrackGain>().map(|it| { it.get() }),
+        track_peak: tag_list.get::<TrackPeak>().map(|it| { it.get() }),
+        album_gain: tag_list.get::<AlbumGain>().map(|it| { it.get() }),
+        album_peak: tag_list.get::<AlbumPeak>().map(|it| { it.get() }),
+        changed: changed(&entry_path.symlink_metadata()?),
+    }))
+}
+
+pub fn tagged_replay_gain(song_path: &Path) -> Option<(f64, Option<f64>, Option<f64>, Option<f64>)> {
+    discover(&DISCOVERER, song_path).ok().flatten().and_then(|discovered| {
+        discovered.track_gain
+            .map(|gain| { (gain, discovered.track_peak, discovered.album_gain, discovered.album_peak) })
+    })
+}
+
+fn is_disc_directory(directory: &Path) -> bool {
+    directory.file_name().and_then(OsStr::to_str).map(str::to_lowercase).map(|name| {
+        (name.starts_with("cd") || name.starts_with("disc") || name.starts_with("disk"))
+            && name.chars().any(|it| { it.is_ascii_digit() })
+    }).unwrap_or(false)
+}
+
+fn album_directory(song_path: &Path) -> PathBuf {
+    let directory = song_path.parent().unwrap_or(Path::new(""));
+    if is_disc_directory(directory) { directory.parent().unwrap_or(Path::new("")) } else { directory }.to_path_buf()
+}
+
+pub fn primary_artist(artist_string: &str) -> &str {
+    let lowercase = artist_string.to_ascii_lowercase();
+    FEATURING.iter().filter_map(|featuring| { lowercase.find(featuring) }).min()
+        .map(|i| { artist_string[..i].trim_end() }).filter(|it| { !it.is_empty() }).unwrap_or(artist_string)
+}
+
+fn tagged_album_artist(album_artist_string: &str) -> &str {
+    if ["various artists", "various", "va"].contains(&album_artist_string.to_lowercase().as_str()) {
+        VARIOUS_ARTISTS
+    } else {
+        album_artist_string
+    }
+}
+
+fn refresh_album_artist(album_id_int: i32, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> QueryResult<usize> {
+    let credits = songs.filter(album_id.eq(album_id_int)).select((album_artist, artist))
+        .get_results::<(Option<String>, Option<String>)>(connection)?;
+    let album_artist_string = if let Some(tagged) = credits.iter().find_map(|(it, _)| { it.as_deref() }) {
+        Some(tagged_album_artist(tagged))
+    } else {
+        let track_artists = credits.iter().map(|(_, it)| { it.as_deref().map(primary_artist) })
+            .collect::<HashSet<_>>();
+        if track_artists.len() > 1 { Some(VARIOUS_ARTISTS) } else { track_artists.into_iter().next().flatten() }
+    };
+    update(albums::table.find(album_id_int)).set(albums::artist.eq(album_artist_string)).execute(connection)
+}
+
+fn existing_album(collection_id_int: i32, album_title: &Option<String>, disambiguator: &str, tagged: Option<&str>,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> QueryResult<Option<Album>> {
+    let statement = albums::table.filter(albums::collection_id.eq(collection_id_int))
+        .filter(albums::title.is(album_title)).filter(albums::disambiguator.eq(disambiguator))
+        .order_by(albums::id).into_boxed();
+    // untagged songs join whichever album is in their directory, its artist is worked out from all of its songs
+    if let Some(tagged) = tagged {
+        statement.filter(albums::artist.is(Some(tagged)))
+    } else {
+        statement
+    }.first::<Album>(connection).optional()
+}
+
+fn upsert_album(relative_path: &Path, discovered: &Discovered, collection_id_int: i32,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> QueryResult<i32> {
+    let directory = album_directory(relative_path);
+    let tagged = discovered.album_artist.as_deref().map(tagged_album_artist);
+    let disambiguator = discovered.musicbrainz_album_id.clone()
+        .unwrap_or_else(|| { directory.to_str().unwrap().to_owned() });
+    if let Some(existing) = existing_album(collection_id_int, &discovered.album, &disambiguator, tagged, connection)? {
+        update(albums::table.find(existing.id)).set((albums::year.eq(discovered.year.or(existing.year)),
+            albums::total_tracks.eq(discovered.total_tracks.or(existing.total_tracks)))).execute(connection)?;
+        Ok(existing.id)
+    } else {
+        insert_into(albums::table).values((
+            albums::collection_id.eq(collection_id_int),
+            albums::artist.eq(tagged.or(discovered.artist.as_deref().map(primary_artist))),
+            albums::title.eq(&discovered.album),
+            albums::disambiguator.eq(&disambiguator),
+            albums::year.eq(discovered.year),
+            albums::cover.eq(directory.join(COVER).to_str().unwrap()),
+            albums::total_tracks.eq(discovered.total_tracks),
+        )).returning(albums::id).get_result::<i32>(connection)
+    }
+}
+
+// moves a song whose album or album artist was edited to the album it now belongs to, keeping the disambiguator
+pub fn reassign_album(song_id: i32, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> QueryResult<()> {
+    let song = songs.find(song_id).get_result::<Song>(connection)?;
+    let previous = song.album_id.map(|it| { albums::table.find(it).get_result::<Album>(connection).optional() })
+        .transpose()?.flatten();
+    let directory = album_directory(song.path.to_path());
+    let disambiguator = previous.as_ref().map(|it| { it.disambiguator.clone() })
+        .unwrap_or_else(|| { directory.to_str().unwrap().to_owned() });
+    let tagged = song.album_artist.as_deref().map(tagged_album_artist);
+    let album_id_int = if let Some(existing)
+        = existing_album(song.collection_id, &song.album, &disambiguator, tagged, connection)? {
+        existing.id
+    } else {
+        insert_into(albums::table).values((
+            albums::collection_id.eq(song.collection_id),
+            albums::artist.eq(tagged.or(song.artist.as_deref().map(primary_artist))),
+            albums::title.eq(&song.album),
+            albums::disambiguator.eq(&disambiguator),
+            albums::year.eq(previous.as_ref().and_then(|it| { it.year }).or(song.year)),
+            albums::cover.eq(previous.as_ref().map(|it| { it.cover.clone() })
+                .unwrap_or_else(|| { directory.join(COVER).to_str().unwrap().to_owned() })),
+            albums::total_tracks.eq(previous.as_ref().and_then(|it| { it.total_tracks })),
+        )).returning(albums::id).get_result::<i32>(connection)?
+    };
+    update(songs.find(song.id)).set(album_id.eq(album_id_int)).execute(connection)?;
+    refresh_album_artist(album_id_int, connection)?;
+    if let Some(previous) = previous.filter(|it| { it.id != album_id_int }) {
+        if songs.filter(album_id.eq(previous.id)).count().get_result::<i64>(connection)? == 0 {
+            delete(albums::table.find(previous.id)).execute(connection)?;
+        } else {
+            refresh_album_artist(previous.id, connection)?;
+        }
+    }
+    Ok(())
+}
+
+fn delete_orphan_albums(connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> QueryResult<usize> {
+    delete(albums::table.filter(not(exists(songs.filter(album_id.eq(albums::id.nullable())))))).execute(connection)
+}
+
+fn upsert(entry_path: &Path, discovered: &Discovered, collection: &Arc<RwLock<Collection>>,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> anyhow::Result<()> {
+    let relative_path = entry_path.strip_prefix(&collection.read().unwrap().path)?;
+    let album_id_int = upsert_album(relative_path, discovered, collection.read().unwrap().id, connection)?;
+    let values = (
+        path.eq(relative_path.to_str().unwrap()),
+        title.eq(&discovered.title),
+        artist.eq(&discovered.artist),
+        album.eq(&discovered.album),
+        year.eq(discovered.year),
+        genre.eq(&discovered.genre),
+        track_number.eq(discovered.track_number),
+        album_volume.eq(discovered.album_volume),
+        album_artist.eq(&discovered.album_artist),
+        duration.eq(discovered.duration),
+        lyrics.eq(&discovered.lyrics),
+        collection_id.eq(collection.read().unwrap().id),
+        album_id.eq(album_id_int),
+        track_gain.eq(discovered.track_gain),
+        track_peak.eq(discovered.track_peak),
+        album_gain.eq(discovered.album_gain),
+        album_peak.eq(discovered.album_peak),
+        analysis_failed.eq(0),
+    );
+    insert_into(songs).values(values).on_conflict(path).do_update().set(values).execute(connection)?;
+    refresh_album_artist(album_id_int, connection)?;
+    delete(import_issues::table.filter(import_issues::collection_id.eq(collection.read().unwrap().id))
+        .filter(import_issues::path.eq(relative_path.to_str().unwrap()))).execute(connection)?;
+    Ok(())
+}
+
+fn record_issue(entry_path: &Path, error: &anyhow::Error, collection: &Arc<RwLock<Collection>>,
+    sender: &Sender<ImportProgress>, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> anyhow::Result<()> {
+    warn!("error importing [{entry_path:?}] [{error}]");
+    let reason = error.to_string();
+    if let Ok(relative_path) = entry_path.strip_prefix(&collection.read().unwrap().path) {
+        insert_into(import_issues::table).values((import_issues::collection_id.eq(collection.read().unwrap().id),
+            import_issues::path.eq(relative_path.to_str().unwrap()), import_issues::reason.eq(&reason)))
+            .on_conflict((import_issues::collection_id, import_issues::path)).do_update()
+            .set(import_issues::reason.eq(&reason)).execute(connection)?;
+    }
+    Ok(sender.send(ImportProgress::Failed(entry_path.to_path_buf(), reason))?)
+}
+
+pub fn import_song(entry_path: PathBuf, collection: Arc<RwLock<Collection>>, sender: Sender<ImportProgress>,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> anyhow::Result<()> {
+    sender.send(ImportProgress::CollectionStart(Arc::new(AtomicBool::new(false))))?;
+    match discover(&DISCOVERER, &entry_path) {
+        Ok(Some(discovered)) => {
+            upsert(&entry_path, &discovered, &collection, connection)?;
+            delete_orphan_albums(connection)?;
+        }
+        Ok(None) => { sender.send(ImportProgress::Skipped(entry_path))?; }
+        Err(error) => { record_issue(&entry_path, &error, &collection, &sender, connection)?; }
+    }
+    Ok(sender.send(ImportProgress::CollectionEnd(collection))?)
+}
+
+fn adopt_moved(candidate: &Path, discovered: &Discovered, missing: &mut Vec<Song>,
+    collection: &Arc<RwLock<Collection>>, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> anyhow::Result<()> {
+    let candidate_path = candidate.strip_prefix(&collection.read().unwrap().path)?.to_str().unwrap().to_owned();
+    let fingerprint = discovered.fingerprint();
+    if let Some(i) = missing.iter().position(|song| { song.fingerprint() == fingerprint }) {
+        if songs.filter(path.eq(&candidate_path)).count().get_result::<i64>(connection)? == 0 {
+            let song = missing.swap_remove(i);
+            info!("song [{}] moved to [{candidate_path}]", song.path);
+            update(songs.find(song.id)).set(path.eq(&candidate_path)).execute(connection)?;
+        }
+    }
+    Ok(())
+}
+
+fn delete_missing(collection: &Arc<RwLock<Collection>>, missing: &Vec<Song>,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> QueryResult<usize> {
+    info!("removing [{}] songs missing from collection [{:?}]", missing.len(), collection.read().unwrap());
+    delete(songs.filter(id.eq_any(missing.iter().map(|song| { song.id }).collect::<Vec<_>>()))).execute(connection)
+}
+
+pub fn reconcile(collection: &Arc<RwLock<Collection>>, mut missing: Vec<Song>, candidates: Vec<PathBuf>,
+    sender: &Sender<ImportProgress>, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> anyhow::Result<()> {
+    for candidate in candidates {
+        match discover(&DISCOVERER, &candidate) {
+            Ok(Some(discovered)) => {
+                adopt_moved(&candidate, &discovered, &mut missing, collection, connection)?;
+                upsert(&candidate, &discovered, collection, connection)?;
+            }
+            Ok(None) => {}
+            Err(error) => { record_issue(&candidate, &error, collection, sender, connection)?; }
+        }
+    }
+    delete_missing(collection, &missing, connection)?;
+    delete_orphan_albums(connection)?;
+    Ok(())
+}
+
+pub fn ignored_paths(collection: &Arc<RwLock<Collection>>,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> QueryResult<HashSet<PathBuf>> {
+    let collection = collection.read().unwrap();
+    Ok(import_issues::table.filter(import_issues::collection_id.eq(collection.id))
+        .filter(import_issues::ignored.eq(1)).select(import_issues::path).get_results::<String>(connection)?
+        .into_iter().map(|issue_path| { join_path(&collection.path, &issue_path) }).collect())
+}
+
+// songs whose files are gone, and the paths of the others
+fn missing_songs(collection: &Arc<RwLock<Collection>>,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>)
+    -> QueryResult<(Vec<Song>, HashSet<PathBuf>)> {
+    let collection_path = collection.read().unwrap().path.clone();
+    let (missing, existing) = songs.filter(collection_id.eq(collection.read().unwrap().id))
+        .get_results::<Song>(connection)?.into_iter()
+        .partition::<Vec<_>, _>(|song| { !join_path(&collection_path, &song.path).exists() });
+    Ok((missing, existing.iter().map(|song| { join_path(&collection_path, &song.path) }).collect()))
+}
+
+fn spawn_workers(entries: Arc<Vec<PathBuf>>, cancel: Arc<AtomicBool>)
+    -> Receiver<(PathBuf, anyhow::Result<Option<Discovered>>)> {
+    let (discovered_sender, discovered_receiver) = channel();
+    let next = Arc::new(AtomicUsize::new(0));
+    for _ in 0..thread::available_parallelism().map(NonZeroUsize::get).unwrap_or(1) {
+        let entries = entries.clone();
+        let cancel = cancel.clone();
+        let next = next.clone();
+        let discovered_sender = discovered_sender.clone();
+        thread::spawn(move || {
+            let discoverer = Discoverer::new(ClockTime::from_seconds(30)).unwrap();
+            while !cancel.load(Ordering::Relaxed) {
+                if let Some(entry_path) = entries.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let discovered = discover(&discoverer, entry_path);
+                    if discovered_sender.send((entry_path.clone(), discovered)).is_err() { break; }
+                } else {
+                    break;
+                }
+            }
+        });
+    }
+    discovered_receiver
+}
+
+fn write_batch(batch: &mut Vec<(PathBuf, Discovered)>, missing: &mut Vec<Song>,
+    collection: &Arc<RwLock<Collection>>, sender: &Sender<ImportProgress>,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> anyhow::Result<Option<SystemTime>> {
+    connection.transaction(|connection| {
+        let mut max_changed = None;
+        for (entry_path, discovered) in batch.drain(..) {
+            if !missing.is_empty() { adopt_moved(&entry_path, &discovered, missing, collection, connection)?; }
+            if let Err(error) = upsert(&entry_path, &discovered, collection, connection) {
+                record_issue(&entry_path, &error, collection, sender, connection)?;
+            } else {
+                max_changed = max(max_changed, discovered.changed);
+            }
+        }
+        anyhow::Ok(max_changed)
+    })
+}
+
 pub fn import_songs(collection: Arc<RwLock<Collection>>, sender: Sender<ImportProgress>,
     connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> anyhow::Result<()> {
-    sender.send(ImportProgress::CollectionStart)?;
+    let cancel = Arc::new(AtomicBool::new(false));
+    sender.send(ImportProgress::CollectionStart(cancel.clone()))?;
+    let (mut missing, known_paths) = missing_songs(&collection, connection)?;
     let last_modified = collection.read().unwrap().modified
         .map(|it| { UNIX_EPOCH.add(Duration::from_nanos(it as u64)) });
-    let total = walk_newer_than(&collection, last_modified).count();
-    info!("importing [{total}] new files to collection [{:?}]", collection.read().unwrap());
-    let total_f64 = total as f64;
-    let count = Arc::new(AtomicUsize::new(0));
-    task::spawn({
-        let count = count.clone();
-        let sender = sender.clone();
-        async move {
-            loop {
-                task::sleep(Duration::from_millis(500)).await;
-                let count = count.load(Ordering::Relaxed);
-                if count == total { break; }
-                sender.send(ImportProgress::Fraction(count as f64 / total_f64)).unwrap();
+    let ignored_paths = ignored_paths(&collection, connection)?;
+    let mut entries = Vec::new();
+    for entry_result in walk_newer_than(&collection, last_modified) {
+        if cancel.load(Ordering::Relaxed) { break; }
+        match entry_result {
+            Ok(entry) => {
+                if entry.file_type().is_file() && !ignored_paths.contains(entry.path()) {
+                    entries.push(entry.into_path());
+                }
+            }
+            Err(error) => {
+                let error_path = error.path().map(Path::to_path_buf).unwrap_or_default();
+                record_issue(&error_path, &anyhow::Error::from(error), &collection, &sender, connection)?;
             }
         }
-    });
-    let max_modified = walk_newer_than(&collection, last_modified).filter_map(|entry_result| {
-        task::block_on({
-            let count = count.clone();
-            async move { count.fetch_add(1, Ordering::Relaxed); }
-        });
-        let entry = entry_result.unwrap();
-        entry.file_type().is_file().then_some(entry)
-    }).map(|entry| -> anyhow::Result<_> {
-        Ok(if let Ok(discoverer_info) = DISCOVERER
-            .discover_uri(format!("file:{}", entry.path().to_str().unwrap()).as_str()) {
-            if discoverer_info.video_streams().is_empty() && !discoverer_info.audio_streams().is_empty() {
-                let tag_list = discoverer_info.tags().unwrap();
-                let title_tag = tag_list.get::<Title>();
-                let artist_tag = tag_list.get::<Artist>();
-                let album_tag = tag_list.get::<Album>();
-                let datetime_tag = tag_list.get::<DateTime>();
-                let genre_tag = tag_list.get::<Genre>();
-                let track_number_tag = tag_list.get::<TrackNumber>();
-                let album_volume_number_tag = tag_list.get::<AlbumVolumeNumber>();
-                let album_artist_tag = tag_list.get::<AlbumArtist>();
-                let lyrics_tag = tag_list.get::<Lyrics>();
-                let values = (
-                    path.eq(entry.path().strip_prefix(&collection.read().unwrap().path)?.to_str().unwrap()),
-                    title.eq(title_tag.as_ref().as_str()),
-                    artist.eq(artist_tag.as_ref().as_str()),
-                    album.eq(album_tag.as_ref().as_str()),
-                    year.eq(datetime_tag.map(|it| { it.get().year() })),
-                    genre.eq(genre_tag.as_ref().as_str()),
-                    track_number.eq(track_number_tag.map(|it| { it.get() as i32 })),
-                    album_volume.eq(album_volume_number_tag.map(|it| { it.get() as i32 })),
-                    album_artist.eq(album_artist_tag.as_ref().as_str()),
-                    duration.eq(discoverer_info.duration().unwrap().nseconds() as i64),
-                    lyrics.eq(lyrics_tag.as_ref().as_str()),
-                    collection_id.eq(collection.read().unwrap().id),
-                );
-                insert_into(songs).values(values).on_conflict(path).do_update().set(values).execute(connection)?;
-                let metadata = entry.metadata()?;
-                Some(max(metadata.created()?, metadata.modified()?))
-            } else {
-                None
+    }
+    if !missing.is_empty() {
+        // moved songs are looked for among the files not imported yet, which the workers discover with the new ones
+        let newer_paths = entries.iter().cloned().collect::<HashSet<_>>();
+        for entry in WalkDir::new(&collection.read().unwrap().path).into_iter().filter_map(Result::ok) {
+            if cancel.load(Ordering::Relaxed) { break; }
+            if entry.file_type().is_file() && !known_paths.contains(entry.path())
+                && !ignored_paths.contains(entry.path()) && !newer_paths.contains(entry.path()) {
+                entries.push(entry.into_path());
             }
-        } else {
-            None
-        })
-    }).try_fold(None, |prev, next| next.map(|ok| max(prev, ok)))?;
-    if let Some(max_modified) = max_modified {
+        }
+    }
+    let total = entries.len();
+    info!("importing [{total}] new files to collection [{:?}]", collection.read().unwrap());
+    let mut max_modified = None;
+    let mut batch = Vec::new();
+    let mut last_progress = Instant::now();
+    for (i, (entry_path, discovered)) in spawn_workers(Arc::new(entries), cancel.clone()).into_iter().enumerate() {
+        match discovered {
+            Ok(Some(discovered)) => { batch.push((entry_path, discovered)); }
+            Ok(None) => { sender.send(ImportProgress::Skipped(entry_path))?; }
+            Err(error) => { record_issue(&entry_path, &error, &collection, &sender, connection)?; }
+        }
+        if batch.len() == BATCH_SIZE {
+            max_modified = max(max_modified, write_batch(&mut batch, &mut missing, &collection, &sender, connection)?);
+        }
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            sender.send(ImportProgress::Fraction((i + 1) as f64 / total as f64))?;
+            last_progress = Instant::now();
+        }
+    }
+    max_modified = max(max_modified, write_batch(&mut batch, &mut missing, &collection, &sender, connection)?);
+    // songs not found elsewhere are only known to be gone once every file was looked at
+    if !cancel.load(Ordering::Relaxed) && !missing.is_empty() { delete_missing(&collection, &missing, connection)?; }
+    delete_orphan_albums(connection)?;
+    if cancel.load(Ordering::Relaxed) {
+        info!("cancelled importing to collection [{:?}]", collection.read().unwrap());
+    } else if let Some(max_modified) = max_modified {
         let max_modified = max_modified.duration_since(UNIX_EPOCH)?.as_nanos() as i64;
         update(collections.find(collection.read().unwrap().id)).set(modified.eq(max_modified)).execute(connection)?;
         collection.write().unwrap().modified = Some(max_modified);
@@ -213,17 +614,39 @@ pub fn get_current_song(connection: &mut PooledConnection<ConnectionManager<Sqli
     songs.inner_join(config).inner_join(collections).get_result::<(Song, Config, Collection)>(connection)
 }
 
-pub fn get_current_album(artist_string: &Option<impl AsRef<String>>, album_string: &Option<impl AsRef<String>>,
+pub fn get_current_album(album_id_option: Option<i32>,
     connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> Vec<(Song, Collection)> {
-    let statement = songs.inner_join(collections).order_by((track_number, id)).into_boxed();
-    let artist_filtered_statement = if let Some(artist_string) = &artist_string {
-        statement.filter(artist.eq(artist_string.as_ref()))
-    } else {
-        statement.filter(artist.is_null())
-    };
-    if let Some(album_string) = &album_string {
-        artist_filtered_statement.filter(album.eq(album_string.as_ref()))
-    } else {
-        artist_filtered_statement.filter(album.is_null())
-    }.get_results::<(Song, Collection)>(connection).unwrap()
+    songs.inner_join(collections).filter(album_id.is(album_id_option)).order_by((album_volume, track_number, id))
+        .get_results::<(Song, Collection)>(connection).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::song::primary_artist;
+
+    #[test]
+    fn primary_artist_drops_featured_artists() {
+        assert_eq!(primary_artist("Daft Punk feat. Pharrell Williams"), "Daft Punk");
+        assert_eq!(primary_artist("Eminem ft. Rihanna"), "Eminem");
+        assert_eq!(primary_artist("Santana featuring Rob Thomas"), "Santana");
+        assert_eq!(primary_artist("Gorillaz (feat. De La Soul)"), "Gorillaz");
+        assert_eq!(primary_artist("Calvin Harris [feat. Florence Welch]"), "Calvin Harris");
+    }
+
+    #[test]
+    fn primary_artist_ignores_the_case_of_the_featuring_word() {
+        assert_eq!(primary_artist("Jay-Z FEAT. Kanye West"), "Jay-Z");
+    }
+
+    #[test]
+    fn primary_artist_cuts_at_the_first_featuring_word() {
+        assert_eq!(primary_artist("A feat. B featuring C"), "A");
+    }
+
+    #[test]
+    fn primary_artist_keeps_names_without_a_featured_artist() {
+        assert_eq!(primary_artist("Featherweight"), "Featherweight");
+        assert_eq!(primary_artist("Simon & Garfunkel"), "Simon & Garfunkel");
+        assert_eq!(primary_artist("feat. Nobody"), "feat. Nobody");
+    }
 }
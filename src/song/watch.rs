@@ -0,0 +1,109 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use diesel::{BoolExpressionMethods, Connection, EscapeExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl,
+    TextExpressionMethods};
+use log::{error, info};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use once_cell::sync::Lazy;
+use walkdir::WalkDir;
+use crate::body::collection::model::Collection;
+use crate::common::util::PathString;
+use crate::db::get_connection;
+use crate::schema::songs::{collection_id, path};
+use crate::schema::songs::dsl::songs;
+use crate::song::{ignored_paths, ImportProgress, reconcile, Song};
+
+static WATCHERS: Lazy<Mutex<HashMap<i32, RecommendedWatcher>>> = Lazy::new(|| { Mutex::new(HashMap::new()) });
+static WRITTEN: Lazy<Mutex<HashMap<PathBuf, SystemTime>>> = Lazy::new(|| { Mutex::new(HashMap::new()) });
+const SETTLE: Duration = Duration::from_secs(1);
+
+fn escape_like(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+// the tags harborz writes are already in the database, unless the file was changed again since
+fn written_by_harborz(song_path: &Path) -> bool {
+    match WRITTEN.lock().unwrap().remove(song_path) {
+        Some(written) => {
+            song_path.metadata().and_then(|metadata| { metadata.modified() }).map(|it| { it <= written })
+                .unwrap_or(false)
+        }
+        None => { false }
+    }
+}
+
+fn sync(changed_paths: BTreeSet<PathBuf>, collection: &Arc<RwLock<Collection>>, sender: &Sender<ImportProgress>)
+    -> anyhow::Result<()> {
+    let synced = get_connection().transaction(|connection| {
+        let ignored_paths = ignored_paths(collection, connection)?;
+        let mut missing = Vec::new();
+        let mut candidates = Vec::new();
+        for changed_path in changed_paths {
+            if changed_path.exists() {
+                for entry in WalkDir::new(changed_path) {
+                    let entry = entry?;
+                    if entry.file_type().is_file() && !ignored_paths.contains(entry.path())
+                        && !written_by_harborz(entry.path()) {
+                        candidates.push(entry.into_path());
+                    }
+                }
+            } else {
+                let relative_path = changed_path.strip_prefix(&collection.read().unwrap().path)?.to_str().unwrap();
+                missing.extend(songs.filter(collection_id.eq(collection.read().unwrap().id)).filter(path
+                    .eq(relative_path).or(path.like(format!("{}/%", escape_like(relative_path))).escape('\\')))
+                    .get_results::<Song>(connection)?);
+            }
+        }
+        if missing.is_empty() && candidates.is_empty() { return anyhow::Ok(false); }
+        info!("syncing [{}] changed files of collection [{:?}]", missing.len() + candidates.len(),
+            collection.read().unwrap());
+        sender.send(ImportProgress::CollectionStart(Arc::new(AtomicBool::new(false))))?;
+        missing.sort_by_key(|song: &Song| { song.id });
+        missing.dedup_by_key(|song| { song.id });
+        reconcile(collection, missing, candidates, sender, connection)?;
+        anyhow::Ok(true)
+    })?;
+    if synced { sender.send(ImportProgress::CollectionEnd(collection.clone()))?; }
+    Ok(())
+}
+
+pub fn mark_written(song_path: &Path) {
+    WRITTEN.lock().unwrap().insert(song_path.to_path_buf(), SystemTime::now());
+}
+
+pub fn watch(collection: Arc<RwLock<Collection>>, sender: Sender<ImportProgress>) -> notify::Result<()> {
+    let (path_sender, path_receiver) = channel::<PathBuf>();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        match event {
+            Ok(event) => {
+                if !event.kind.is_access() {
+                    for changed_path in event.paths {
+                        if path_sender.send(changed_path).is_err() { break; }
+                    }
+                }
+            }
+            Err(error) => { error!("error watching collection [{error}]"); }
+        }
+    })?;
+    watcher.watch(collection.read().unwrap().path.to_path(), RecursiveMode::Recursive)?;
+    WATCHERS.lock().unwrap().insert(collection.read().unwrap().id, watcher);
+    thread::spawn(move || {
+        while let Ok(changed_path) = path_receiver.recv() {
+            let mut changed_paths = BTreeSet::from([changed_path]);
+            while let Ok(changed_path) = path_receiver.recv_timeout(SETTLE) { changed_paths.insert(changed_path); }
+            if let Err(error) = sync(changed_paths, &collection, &sender) {
+                error!("error syncing collection [{:?}] [{error}]", collection.read().unwrap());
+            }
+        }
+    });
+    Ok(())
+}
+
+pub fn unwatch(id: i32) {
+    WATCHERS.lock().unwrap().remove(&id);
+}
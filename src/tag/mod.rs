@@ -0,0 +1,148 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use anyhow::anyhow;
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection, update};
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use log::{error, info};
+use crate::body::collection::model::Collection;
+use crate::db::get_connection;
+use crate::schema::collections::dsl::collections;
+use crate::schema::songs::*;
+use crate::schema::songs::dsl::songs;
+use crate::song::{reassign_album, Song, WithPath};
+use crate::tag::writer::{ReplayGain, tag_writer, TagWriter};
+
+pub mod writer;
+
+#[derive(Debug, Clone, Copy, PartialEq, diesel_derive_enum::DbEnum)]
+pub enum Field {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Year,
+    Genre,
+    TrackNumber,
+    AlbumVolume,
+    Lyrics,
+}
+
+pub const FIELDS: [Field; 9] = [Field::Title, Field::Artist, Field::Album, Field::AlbumArtist, Field::Year,
+    Field::Genre, Field::TrackNumber, Field::AlbumVolume, Field::Lyrics];
+
+pub type TagEdit = Vec<(Field, Option<String>)>;
+
+pub enum WriteProgress {
+    Fraction(f64),
+    Failed(String),
+}
+
+fn number(value: &Option<String>) -> anyhow::Result<Option<i32>> {
+    Ok(value.as_deref().map(str::parse::<i32>).transpose()?)
+}
+
+impl Field {
+    pub fn label(self) -> &'static str {
+        match self {
+            Field::Title => { "Title" }
+            Field::Artist => { "Artist" }
+            Field::Album => { "Album" }
+            Field::AlbumArtist => { "Album artist" }
+            Field::Year => { "Year" }
+            Field::Genre => { "Genre" }
+            Field::TrackNumber => { "Track" }
+            Field::AlbumVolume => { "Disc" }
+            Field::Lyrics => { "Lyrics" }
+        }
+    }
+    pub fn numeric(self) -> bool {
+        matches!(self, Field::Year | Field::TrackNumber | Field::AlbumVolume)
+    }
+    pub fn value(self, song: &Song) -> Option<String> {
+        match self {
+            Field::Title => { song.title.clone() }
+            Field::Artist => { song.artist.clone() }
+            Field::Album => { song.album.clone() }
+            Field::AlbumArtist => { song.album_artist.clone() }
+            Field::Year => { song.year.map(|it| { it.to_string() }) }
+            Field::Genre => { song.genre.clone() }
+            Field::TrackNumber => { song.track_number.map(|it| { it.to_string() }) }
+            Field::AlbumVolume => { song.album_volume.map(|it| { it.to_string() }) }
+            Field::Lyrics => { song.lyrics.clone() }
+        }
+    }
+    pub fn update_song(self, song_id: i32, value: &Option<String>,
+        connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> anyhow::Result<usize> {
+        connection.transaction(|connection| {
+            let statement = update(songs.find(song_id));
+            let count = match self {
+                Field::Title => { statement.set(title.eq(value)).execute(connection)? }
+                Field::Artist => { statement.set(artist.eq(value)).execute(connection)? }
+                Field::Album => { statement.set(album.eq(value)).execute(connection)? }
+                Field::AlbumArtist => { statement.set(album_artist.eq(value)).execute(connection)? }
+                Field::Year => { statement.set(year.eq(number(value)?)).execute(connection)? }
+                Field::Genre => { statement.set(genre.eq(value)).execute(connection)? }
+                Field::TrackNumber => { statement.set(track_number.eq(number(value)?)).execute(connection)? }
+                Field::AlbumVolume => { statement.set(album_volume.eq(number(value)?)).execute(connection)? }
+                Field::Lyrics => { statement.set(lyrics.eq(value)).execute(connection)? }
+            };
+            if matches!(self, Field::Album | Field::AlbumArtist) { reassign_album(song_id, connection)?; }
+            anyhow::Ok(count)
+        })
+    }
+}
+
+pub fn changes(song: &Song, edit: &TagEdit) -> Vec<(Field, Option<String>, Option<String>)> {
+    edit.iter().filter_map(|(field, value)| {
+        let current = field.value(song);
+        (current != *value).then(|| { (*field, current, value.clone()) })
+    }).collect()
+}
+
+// files already written are restored when one fails so that the caller's transaction can roll back the rows
+pub fn write_song_edits(song_edits: &[(i32, TagEdit)], sender: &Sender<WriteProgress>,
+    connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> anyhow::Result<()> {
+    let mut written = Vec::<(PathBuf, Box<dyn TagWriter>)>::new();
+    let mut write = |connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>| {
+        let total = song_edits.len();
+        for (i, (song_id, edit)) in song_edits.iter().enumerate() {
+            let (song, collection) = songs.inner_join(collections).filter(id.eq(song_id))
+                .get_result::<(Song, Collection)>(connection)?;
+            let song_path = (&song, &collection).path();
+            let mut writer = tag_writer(&song_path)
+                .map_err(|error| { anyhow!("{} [{}]", song_path.to_str().unwrap(), error) })?;
+            for (field, value) in edit {
+                writer.set(*field, value)?;
+                field.update_song(song.id, value, connection)?;
+            }
+            writer.write(&song_path)?;
+            written.push((song_path, writer));
+            sender.send(WriteProgress::Fraction((i + 1) as f64 / total as f64))?;
+        }
+        anyhow::Ok(())
+    };
+    let result = write(connection);
+    if result.is_err() {
+        for (song_path, writer) in written {
+            if let Err(error) = writer.restore(&song_path) {
+                error!("error restoring tags of [{song_path:?}] [{error}]");
+            }
+        }
+    }
+    result
+}
+
+pub fn write_tags(song_edits: &[(i32, TagEdit)], sender: &Sender<WriteProgress>) -> anyhow::Result<()> {
+    get_connection().transaction(|connection| {
+        write_song_edits(song_edits, sender, connection)?;
+        info!("wrote [{}] tags to [{}] songs", song_edits.iter().map(|(_, edit)| { edit.len() }).sum::<usize>(),
+            song_edits.len());
+        anyhow::Ok(())
+    })
+}
+
+pub fn write_replay_gain(song_path: &Path, values: &[(ReplayGain, f64)]) -> anyhow::Result<()> {
+    let mut writer = tag_writer(song_path)?;
+    for (replay_gain, value) in values { writer.set_replay_gain(*replay_gain, *value); }
+    writer.write(song_path)
+}
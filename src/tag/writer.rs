@@ -0,0 +1,242 @@
+use std::path::Path;
+use anyhow::anyhow;
+use id3::{ErrorKind::NoTag, TagLike, Timestamp, Version};
+use id3::frame::{ExtendedText, Lyrics};
+use id3::v1v2::write_to_path;
+use lofty::{Accessor, FileType, ItemKey, Probe, TagExt, TaggedFileExt, TagType};
+use crate::song::watch::mark_written;
+use crate::tag::{Field, number};
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReplayGain {
+    TrackGain,
+    TrackPeak,
+    AlbumGain,
+    AlbumPeak,
+}
+
+impl ReplayGain {
+    fn description(self) -> &'static str {
+        match self {
+            ReplayGain::TrackGain => { "REPLAYGAIN_TRACK_GAIN" }
+            ReplayGain::TrackPeak => { "REPLAYGAIN_TRACK_PEAK" }
+            ReplayGain::AlbumGain => { "REPLAYGAIN_ALBUM_GAIN" }
+            ReplayGain::AlbumPeak => { "REPLAYGAIN_ALBUM_PEAK" }
+        }
+    }
+    fn format(self, value: f64) -> String {
+        match self {
+            ReplayGain::TrackGain | ReplayGain::AlbumGain => { format!("{value:.2} dB") }
+            ReplayGain::TrackPeak | ReplayGain::AlbumPeak => { format!("{value:.6}") }
+        }
+    }
+    fn item_key(self) -> ItemKey {
+        match self {
+            ReplayGain::TrackGain => { ItemKey::ReplayGainTrackGain }
+            ReplayGain::TrackPeak => { ItemKey::ReplayGainTrackPeak }
+            ReplayGain::AlbumGain => { ItemKey::ReplayGainAlbumGain }
+            ReplayGain::AlbumPeak => { ItemKey::ReplayGainAlbumPeak }
+        }
+    }
+}
+
+pub trait TagWriter {
+    fn get(&self, field: Field) -> Option<String>;
+    fn set(&mut self, field: Field, value: &Option<String>) -> anyhow::Result<()>;
+    fn set_replay_gain(&mut self, replay_gain: ReplayGain, value: f64);
+    fn write(&self, song_path: &Path) -> anyhow::Result<()>;
+    fn restore(&self, song_path: &Path) -> anyhow::Result<()>;
+}
+
+struct Id3Writer {
+    original: Option<id3::Tag>,
+    tag: id3::Tag,
+}
+
+impl Id3Writer {
+    fn read(song_path: &Path) -> anyhow::Result<Self> {
+        let original = match id3::Tag::read_from_path(song_path) {
+            Ok(tag) => { Some(tag) }
+            Err(error) => { if let NoTag = error.kind { None } else { return Err(error.into()); } }
+        };
+        let tag = original.clone().unwrap_or_else(id3::Tag::new);
+        Ok(Id3Writer { original, tag })
+    }
+    fn version(&self) -> Version {
+        self.original.as_ref().map(id3::Tag::version).unwrap_or(Version::Id3v24)
+    }
+}
+
+impl TagWriter for Id3Writer {
+    fn get(&self, field: Field) -> Option<String> {
+        let tag = &self.tag;
+        match field {
+            Field::Title => { tag.title().map(String::from) }
+            Field::Artist => { tag.artist().map(String::from) }
+            Field::Album => { tag.album().map(String::from) }
+            Field::AlbumArtist => { tag.album_artist().map(String::from) }
+            Field::Genre => { tag.genre().map(String::from) }
+            Field::Year => { tag.date_recorded().map(|it| { it.year }).or(tag.year()).map(|it| { it.to_string() }) }
+            Field::TrackNumber => { tag.track().map(|it| { it.to_string() }) }
+            Field::AlbumVolume => { tag.disc().map(|it| { it.to_string() }) }
+            Field::Lyrics => { tag.lyrics().next().map(|it| { it.text.clone() }) }
+        }
+    }
+    fn set(&mut self, field: Field, value: &Option<String>) -> anyhow::Result<()> {
+        let tag = &mut self.tag;
+        match (field, value) {
+            (Field::Title, Some(value)) => { tag.set_title(value); }
+            (Field::Title, None) => { tag.remove_title(); }
+            (Field::Artist, Some(value)) => { tag.set_artist(value); }
+            (Field::Artist, None) => { tag.remove_artist(); }
+            (Field::Album, Some(value)) => { tag.set_album(value); }
+            (Field::Album, None) => { tag.remove_album(); }
+            (Field::AlbumArtist, Some(value)) => { tag.set_album_artist(value); }
+            (Field::AlbumArtist, None) => { tag.remove_album_artist(); }
+            (Field::Genre, Some(value)) => { tag.set_genre(value); }
+            (Field::Genre, None) => { tag.remove_genre(); }
+            (Field::Year, _) => {
+                tag.remove_year();
+                tag.remove_date_recorded();
+                if let Some(year_int) = number(value)? {
+                    tag.set_date_recorded(Timestamp {
+                        year: year_int,
+                        month: None,
+                        day: None,
+                        hour: None,
+                        minute: None,
+                        second: None,
+                    });
+                }
+            }
+            (Field::TrackNumber, _) => {
+                if let Some(track) = number(value)? { tag.set_track(track as u32); } else { tag.remove_track(); }
+            }
+            (Field::AlbumVolume, _) => {
+                if let Some(disc) = number(value)? { tag.set_disc(disc as u32); } else { tag.remove_disc(); }
+            }
+            (Field::Lyrics, _) => {
+                tag.remove_all_lyrics();
+                if let Some(value) = value {
+                    tag.add_frame(Lyrics {
+                        lang: String::from("eng"),
+                        description: String::new(),
+                        text: value.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+    fn set_replay_gain(&mut self, replay_gain: ReplayGain, value: f64) {
+        self.tag.add_frame(ExtendedText {
+            description: String::from(replay_gain.description()),
+            value: replay_gain.format(value),
+        });
+    }
+    fn write(&self, song_path: &Path) -> anyhow::Result<()> {
+        write_to_path(song_path, &self.tag, self.version())?;
+        mark_written(song_path);
+        Ok(())
+    }
+    fn restore(&self, song_path: &Path) -> anyhow::Result<()> {
+        match &self.original {
+            Some(tag) => { write_to_path(song_path, tag, tag.version())?; }
+            None => { id3::Tag::remove_from_path(song_path)?; }
+        }
+        mark_written(song_path);
+        Ok(())
+    }
+}
+
+struct LoftyWriter {
+    tag_type: TagType,
+    original: Option<lofty::Tag>,
+    tag: lofty::Tag,
+}
+
+impl LoftyWriter {
+    fn read(song_path: &Path, tag_type: TagType) -> anyhow::Result<Self> {
+        let original = Probe::open(song_path)?.guess_file_type()?.read()?.tag(tag_type).cloned();
+        let tag = original.clone().unwrap_or_else(|| { lofty::Tag::new(tag_type) });
+        Ok(LoftyWriter { tag_type, original, tag })
+    }
+}
+
+fn item_key(field: Field) -> Option<ItemKey> {
+    match field {
+        Field::Title => { Some(ItemKey::TrackTitle) }
+        Field::Artist => { Some(ItemKey::TrackArtist) }
+        Field::Album => { Some(ItemKey::AlbumTitle) }
+        Field::AlbumArtist => { Some(ItemKey::AlbumArtist) }
+        Field::Genre => { Some(ItemKey::Genre) }
+        Field::Lyrics => { Some(ItemKey::Lyrics) }
+        Field::Year | Field::TrackNumber | Field::AlbumVolume => { None }
+    }
+}
+
+impl TagWriter for LoftyWriter {
+    fn get(&self, field: Field) -> Option<String> {
+        let tag = &self.tag;
+        match field {
+            Field::Year => {
+                tag.get_string(&ItemKey::RecordingDate).and_then(|it| { it.get(..4) }).map(String::from)
+                    .or_else(|| { tag.year().map(|it| { it.to_string() }) })
+            }
+            Field::TrackNumber => { tag.track().map(|it| { it.to_string() }) }
+            Field::AlbumVolume => { tag.disk().map(|it| { it.to_string() }) }
+            _ => { tag.get_string(&item_key(field).unwrap()).map(String::from) }
+        }
+    }
+    fn set(&mut self, field: Field, value: &Option<String>) -> anyhow::Result<()> {
+        let tag = &mut self.tag;
+        match field {
+            Field::Year => {
+                tag.remove_year();
+                tag.remove_key(&ItemKey::RecordingDate);
+                if let Some(year_int) = number(value)? {
+                    tag.insert_text(ItemKey::RecordingDate, year_int.to_string());
+                }
+            }
+            Field::TrackNumber => {
+                if let Some(track) = number(value)? { tag.set_track(track as u32); } else { tag.remove_track(); }
+            }
+            Field::AlbumVolume => {
+                if let Some(disc) = number(value)? { tag.set_disk(disc as u32); } else { tag.remove_disk(); }
+            }
+            _ => {
+                let key = item_key(field).unwrap();
+                tag.remove_key(&key);
+                if let Some(value) = value { tag.insert_text(key, value.clone()); }
+            }
+        }
+        Ok(())
+    }
+    fn set_replay_gain(&mut self, replay_gain: ReplayGain, value: f64) {
+        self.tag.insert_text(replay_gain.item_key(), replay_gain.format(value));
+    }
+    fn write(&self, song_path: &Path) -> anyhow::Result<()> {
+        self.tag.save_to_path(song_path)?;
+        mark_written(song_path);
+        Ok(())
+    }
+    fn restore(&self, song_path: &Path) -> anyhow::Result<()> {
+        match &self.original {
+            Some(tag) => { tag.save_to_path(song_path)?; }
+            None => { self.tag_type.remove_from_path(song_path)?; }
+        }
+        mark_written(song_path);
+        Ok(())
+    }
+}
+
+pub fn tag_writer(song_path: &Path) -> anyhow::Result<Box<dyn TagWriter>> {
+    match Probe::open(song_path)?.guess_file_type()?.file_type() {
+        Some(FileType::Mpeg) => { Ok(Box::new(Id3Writer::read(song_path)?)) }
+        Some(FileType::Flac | FileType::Vorbis | FileType::Opus) => {
+            Ok(Box::new(LoftyWriter::read(song_path, TagType::VorbisComments)?))
+        }
+        Some(FileType::Mp4) => { Ok(Box::new(LoftyWriter::read(song_path, TagType::Mp4Ilst)?)) }
+        file_type => { Err(anyhow!("writing tags to [{file_type:?}] files is not supported")) }
+    }
+}